@@ -0,0 +1,426 @@
+use log::info;
+use web_time::Instant;
+
+use crate::dancing_links::{LIMIT_CHECK_INTERVAL, SearchStep};
+use crate::search_stats::SearchStats;
+use crate::solve_options::{SolveOptions, SolveOutcome};
+
+/// Exact cover solver keeping the covered columns in a `u128` bitset, much faster
+/// than `DancingLinks` on the small problems it supports.
+///
+/// The matrix is that of a tiling: its first columns are each covered between `lo`
+/// and `hi` times, like the columns of the pieces, and the others, at most 128,
+/// exactly once, like the cells of the board. Each row covers at most one of the
+/// first columns and at least one of the others. The search fills the first cell not
+/// yet covered with each row whose first cell it is in turn.
+///
+/// Solutions are the same as those of `DancingLinks` on the same matrix, in another
+/// order, and row indices are the same.
+#[derive(Clone, Debug)]
+pub struct Bitboard {
+    /// Mask of the cells of the board
+    full: u128,
+    /// Minimum and maximum number of times each of the first columns is covered
+    quantities: Vec<(usize, usize)>,
+    /// Cells covered by each row, and the first column it covers if any
+    rows: Vec<(u128, Option<usize>)>,
+    /// Rows whose first cell is each cell, in increasing order
+    rows_by_first_cell: Vec<Vec<usize>>,
+    covered: u128,
+    /// Number of times each of the first columns is covered
+    counts: Vec<usize>,
+    stack: Vec<Level>,
+    state: State,
+    stats: SearchStats,
+}
+
+/// Cell filled at a level of the search, and position of the next row to try there.
+#[derive(Clone, Copy, Debug)]
+struct Level {
+    cell: usize,
+    next: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    AtSolution,
+    Descending,
+    Backtracking,
+    Exhausted,
+}
+
+impl Bitboard {
+    /// Builds the solver for the matrix whose rows cover the columns `rows[i]`, the
+    /// first `quantities.len()` columns being covered between `quantities[j].0` and
+    /// `quantities[j].1` times. Returns `None` if the matrix is not of the supported
+    /// shape, see `Bitboard`.
+    pub fn new(quantities: &[(usize, usize)], n_cols: usize, rows: &[Vec<usize>]) -> Option<Self> {
+        let n_counted = quantities.len();
+        let n_cells = n_cols.checked_sub(n_counted)?;
+        if n_cells > 128 {
+            return None;
+        }
+        let mut encoded = Vec::with_capacity(rows.len());
+        let mut rows_by_first_cell = vec![Vec::new(); n_cells];
+        for (index, row) in rows.iter().enumerate() {
+            let (counted, cells): (Vec<usize>, Vec<usize>) =
+                row.iter().partition(|&&column| column < n_counted);
+            let mut mask = 0u128;
+            for &column in &cells {
+                let bit = 1u128 << column.checked_sub(n_counted).filter(|&c| c < n_cells)?;
+                if mask & bit != 0 {
+                    return None;
+                }
+                mask |= bit;
+            }
+            if mask == 0 || counted.len() > 1 {
+                return None;
+            }
+            rows_by_first_cell[mask.trailing_zeros() as usize].push(index);
+            encoded.push((mask, counted.first().copied()));
+        }
+        Some(Self {
+            full: if n_cells == 128 {
+                u128::MAX
+            } else {
+                (1 << n_cells) - 1
+            },
+            quantities: quantities.to_vec(),
+            rows: encoded,
+            rows_by_first_cell,
+            covered: 0,
+            counts: vec![0; n_counted],
+            stack: Vec::new(),
+            state: State::Idle,
+            stats: SearchStats::default(),
+        })
+    }
+
+    pub fn solve(&mut self) -> Option<Vec<usize>> {
+        self.solutions().next()
+    }
+
+    /// Lazily enumerates every exact cover, like `DancingLinks::solutions`.
+    pub fn solutions(&mut self) -> BitboardSolutions<'_> {
+        self.reset_search();
+        BitboardSolutions { bitboard: self }
+    }
+
+    /// Counts every exact cover without materializing any of them.
+    pub fn count_solutions(&mut self) -> u128 {
+        self.reset_search();
+        let mut count: u128 = 0;
+        while self.advance() {
+            count += 1;
+        }
+        self.reset_search();
+        count
+    }
+
+    /// Searches for an exact cover, giving up once one of the limits in `options` is
+    /// hit, see `DancingLinks::solve_with_options`.
+    pub fn solve_with_options(&mut self, options: &SolveOptions) -> SolveOutcome<Vec<usize>> {
+        self.reset_search();
+        self.stats = SearchStats::default();
+        let outcome = loop {
+            if let Some(limit) = options.reached_limit(self.stats.nodes) {
+                info!(
+                    "search stopped by {:?} after {} nodes",
+                    limit, self.stats.nodes
+                );
+                break SolveOutcome::LimitReached {
+                    limit,
+                    nodes: self.stats.nodes,
+                };
+            }
+            let max_steps = options.max_nodes.map_or(LIMIT_CHECK_INTERVAL, |max_nodes| {
+                (max_nodes - self.stats.nodes).min(LIMIT_CHECK_INTERVAL)
+            });
+            match self.step(max_steps) {
+                SearchStep::Solution(solution) => break SolveOutcome::Solved(solution),
+                SearchStep::Exhausted => break SolveOutcome::Unsolvable,
+                SearchStep::Paused => {}
+            }
+        };
+        self.reset_search();
+        outcome
+    }
+
+    /// Returns the statistics of the search in progress or of the last one. Nodes
+    /// are never unlinked, so `updates` stays 0.
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    /// Runs the search for at most `max_steps` steps, see `DancingLinks::step`.
+    pub fn step(&mut self, max_steps: u64) -> SearchStep {
+        self.run(max_steps);
+        match self.state {
+            State::AtSolution => SearchStep::Solution(self.current_rows()),
+            State::Exhausted => SearchStep::Exhausted,
+            _ => SearchStep::Paused,
+        }
+    }
+
+    /// Undoes every level of an in-progress search.
+    pub fn reset_search(&mut self) {
+        self.stack.clear();
+        self.covered = 0;
+        self.counts.fill(0);
+        self.state = State::Idle;
+    }
+
+    /// Advances the search to the next exact cover and returns its row indices.
+    pub(crate) fn next_solution(&mut self) -> Option<Vec<usize>> {
+        if self.advance() {
+            Some(self.current_rows())
+        } else {
+            None
+        }
+    }
+
+    fn advance(&mut self) -> bool {
+        self.run(u64::MAX);
+        self.state == State::AtSolution
+    }
+
+    fn run(&mut self, max_steps: u64) {
+        if self.state == State::Idle {
+            self.stats = SearchStats::default();
+        }
+        let started = Instant::now();
+        self.run_steps(max_steps);
+        self.stats.elapsed += started.elapsed();
+    }
+
+    /// The search loop of `run`, which keeps its state in `self.stack` like
+    /// `DancingLinks::run_steps`.
+    ///
+    /// Each level fills the first cell not yet covered, so a row can only be tried
+    /// there if that cell is its first one.
+    fn run_steps(&mut self, max_steps: u64) {
+        let mut descend = match self.state {
+            State::Idle | State::Descending => true,
+            State::AtSolution | State::Backtracking => false,
+            State::Exhausted => return,
+        };
+
+        let mut steps = 0;
+        loop {
+            if steps == max_steps {
+                self.state = if descend {
+                    State::Descending
+                } else {
+                    State::Backtracking
+                };
+                return;
+            }
+            steps += 1;
+
+            if descend {
+                let depth = self.stack.len();
+                if depth >= self.stats.nodes_per_depth.len() {
+                    self.stats.nodes_per_depth.resize(depth + 1, 0);
+                }
+                self.stats.nodes_per_depth[depth] += 1;
+                self.stats.nodes += 1;
+
+                if self.covered == self.full {
+                    let mut quantities = self.counts.iter().zip(&self.quantities);
+                    if quantities.all(|(&count, &(lo, _))| count >= lo) {
+                        self.stats.solutions += 1;
+                        self.state = State::AtSolution;
+                        return;
+                    }
+                    descend = false;
+                    continue;
+                }
+
+                let cell = (!self.covered & self.full).trailing_zeros() as usize;
+                self.stack.push(Level { cell, next: 0 });
+                descend = self.try_next_row();
+            } else {
+                let Some(level) = self.stack.last() else {
+                    self.state = State::Exhausted;
+                    return;
+                };
+                self.stats.backtracks += 1;
+                let row = self.rows_by_first_cell[level.cell][level.next - 1];
+                self.deselect(row);
+                descend = self.try_next_row();
+            }
+        }
+    }
+
+    /// Selects the next row that fits at the deepest level, returning whether there
+    /// was one. When none is left, the level is popped.
+    fn try_next_row(&mut self) -> bool {
+        let level = self.stack.last_mut().unwrap();
+        let candidates = &self.rows_by_first_cell[level.cell];
+        while level.next < candidates.len() {
+            let row = candidates[level.next];
+            level.next += 1;
+            let (mask, counted) = self.rows[row];
+            let fits = counted.is_none_or(|c| self.counts[c] < self.quantities[c].1);
+            if fits && mask & self.covered == 0 {
+                self.covered |= mask;
+                if let Some(counted) = counted {
+                    self.counts[counted] += 1;
+                }
+                return true;
+            }
+        }
+        self.stack.pop();
+        false
+    }
+
+    fn deselect(&mut self, row: usize) {
+        let (mask, counted) = self.rows[row];
+        self.covered &= !mask;
+        if let Some(counted) = counted {
+            self.counts[counted] -= 1;
+        }
+    }
+
+    fn current_rows(&self) -> Vec<usize> {
+        self.stack
+            .iter()
+            .map(|level| self.rows_by_first_cell[level.cell][level.next - 1])
+            .collect()
+    }
+}
+
+/// Iterator over all exact covers of a `Bitboard`.
+///
+/// Returned by [`Bitboard::solutions`].
+pub struct BitboardSolutions<'a> {
+    bitboard: &'a mut Bitboard,
+}
+
+impl Iterator for BitboardSolutions<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.bitboard.next_solution()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dancing_links::DancingLinks;
+    use crate::rng::SplitMix64;
+
+    fn sorted(solutions: impl Iterator<Item = Vec<usize>>) -> Vec<Vec<usize>> {
+        let mut solutions: Vec<Vec<usize>> = solutions
+            .map(|mut solution| {
+                solution.sort();
+                solution
+            })
+            .collect();
+        solutions.sort();
+        solutions
+    }
+
+    #[test]
+    fn test_matches_dancing_links() {
+        let mut rng = SplitMix64::new(0x9e37_79b9_7f4a_7c15);
+
+        let mut n_solutions = 0;
+        for _ in 0..200 {
+            let n_counted = rng.below(3);
+            let n_cells = 1 + rng.below(8);
+            let quantities: Vec<(usize, usize)> = (0..n_counted)
+                .map(|_| {
+                    let lo = rng.below(2);
+                    (lo, lo + 1 + rng.below(2))
+                })
+                .collect();
+            let rows: Vec<Vec<usize>> = (0..1 + rng.below(12))
+                .map(|_| {
+                    let mut row: Vec<usize> = (0..n_cells)
+                        .filter(|_| rng.below(3) == 0)
+                        .map(|cell| n_counted + cell)
+                        .collect();
+                    if row.is_empty() {
+                        row.push(n_counted + rng.below(n_cells));
+                    }
+                    if n_counted > 0 && rng.below(4) != 0 {
+                        row.insert(0, rng.below(n_counted));
+                    }
+                    row
+                })
+                .collect();
+
+            let mut dlx = DancingLinks::new();
+            for &(lo, hi) in &quantities {
+                dlx.append_column_with_multiplicity(lo, hi).unwrap();
+            }
+            (0..n_cells).for_each(|_| dlx.append_column());
+            for row in &rows {
+                dlx.append_sparse_row(row).unwrap();
+            }
+            let mut bitboard = Bitboard::new(&quantities, n_counted + n_cells, &rows).unwrap();
+            let expected = sorted(dlx.solutions());
+            n_solutions += expected.len();
+            assert_eq!(sorted(bitboard.solutions()), expected, "{:?}", rows);
+            assert_eq!(bitboard.count_solutions(), expected.len() as u128);
+            assert_eq!(bitboard.stats().solutions, expected.len() as u64);
+            match bitboard.solve_with_options(&SolveOptions::default()) {
+                SolveOutcome::Solved(solution) => {
+                    assert!(expected.contains(&sorted(std::iter::once(solution))[0]))
+                }
+                outcome => assert!(expected.is_empty(), "{:?}", outcome),
+            }
+        }
+        assert!(n_solutions > 100);
+    }
+
+    #[test]
+    fn test_step_and_limits() {
+        // Dominoes on a 2x4 board, cells numbered row by row after the piece column
+        let mut rows = Vec::new();
+        for cell in 0..8 {
+            if cell % 4 != 3 {
+                rows.push(vec![0, 1 + cell, 2 + cell]);
+            }
+            if cell < 4 {
+                rows.push(vec![0, 1 + cell, 5 + cell]);
+            }
+        }
+        let mut bitboard = Bitboard::new(&[(4, 4)], 9, &rows).unwrap();
+        assert_eq!(bitboard.count_solutions(), 5);
+
+        let mut found = 0;
+        loop {
+            match bitboard.step(1) {
+                SearchStep::Solution(solution) => {
+                    assert_eq!(solution.len(), 4);
+                    found += 1;
+                }
+                SearchStep::Paused => {}
+                SearchStep::Exhausted => break,
+            }
+        }
+        assert_eq!(found, 5);
+
+        let options = SolveOptions {
+            max_nodes: Some(2),
+            ..Default::default()
+        };
+        assert!(matches!(
+            bitboard.solve_with_options(&options),
+            SolveOutcome::LimitReached { nodes: 2, .. }
+        ));
+        // Too few copies of the piece to cover the board
+        let mut bitboard = Bitboard::new(&[(0, 3)], 9, &rows).unwrap();
+        assert_eq!(bitboard.solve(), None);
+
+        assert!(Bitboard::new(&[], 129, &[vec![0]]).is_none());
+        assert!(Bitboard::new(&[(1, 1)], 3, &[vec![0]]).is_none());
+        assert!(Bitboard::new(&[(1, 1), (1, 1)], 3, &[vec![0, 1, 2]]).is_none());
+        assert!(Bitboard::new(&[], 3, &[vec![3]]).is_none());
+        assert!(Bitboard::new(&[], 128, &[(0..128).collect()]).is_some());
+    }
+}
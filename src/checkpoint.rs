@@ -0,0 +1,122 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dancing_links::SearchState;
+use crate::search_stats::SearchStats;
+
+/// Snapshot of a `DancingLinks` matrix and of the position of its search.
+///
+/// Taken with `DancingLinks::checkpoint`, it holds everything needed to rebuild the
+/// matrix and go on with the search, so a long enumeration can be saved to disk and
+/// resumed after the process stops.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub(crate) columns: Vec<ColumnSpec>,
+    /// Entries of each row, as column index and color
+    pub(crate) rows: Vec<Vec<(usize, u32)>>,
+    /// Labels of the columns and rows, see `DancingLinks::set_column_label`
+    #[serde(default)]
+    pub(crate) column_labels: Vec<Option<String>>,
+    #[serde(default)]
+    pub(crate) row_labels: Vec<Option<String>>,
+    /// Columns and rows taken out with `DancingLinks::remove_column` and `remove_row`,
+    /// which `columns` and `rows` still list
+    #[serde(default)]
+    pub(crate) removed_columns: Vec<usize>,
+    #[serde(default)]
+    pub(crate) removed_rows: Vec<usize>,
+    /// Rows of each column, in the order the search tries them
+    pub(crate) row_order: Vec<Vec<usize>>,
+    /// Column branched on at each level of the search and row taken from it, `None`
+    /// when the column is left out
+    pub(crate) path: Vec<(usize, Option<usize>)>,
+    /// Number of levels at the start of `path` selected by `DancingLinks::force_rows`,
+    /// which the search does not backtrack
+    #[serde(default)]
+    pub(crate) floor: usize,
+    pub(crate) state: SearchState,
+    pub(crate) stats: SearchStats,
+}
+
+/// A column as it was appended to the matrix.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ColumnSpec {
+    pub(crate) secondary: bool,
+    /// Minimum and maximum number of times the column is covered
+    pub(crate) lo: usize,
+    pub(crate) hi: usize,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint to `path` as JSON.
+    ///
+    /// The file is replaced atomically, so a crash while saving leaves the previous
+    /// checkpoint intact.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&temporary, path)
+    }
+
+    /// Reads a checkpoint written by `save`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Returns the statistics of the search up to the checkpoint.
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    /// Whether the search had explored every branch when the checkpoint was taken.
+    pub fn is_exhausted(&self) -> bool {
+        self.state == SearchState::Exhausted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dancing_links::{DancingLinks, SearchStep};
+
+    #[test]
+    fn test_checkpoint_save_and_load() {
+        let _ = env_logger::try_init();
+        let mut dlx = DancingLinks::new();
+        dlx.append_column_with_multiplicity(1, 2).unwrap();
+        dlx.append_column();
+        dlx.append_secondary_column();
+        dlx.append_colored_row(&[1, 0, 1], &[0, 0, 3]).unwrap();
+        dlx.append_row(&[1, 1, 0]).unwrap();
+        dlx.append_row(&[0, 1, 1]).unwrap();
+        dlx.append_colored_row(&[1, 0, 1], &[0, 0, 3]).unwrap();
+        assert_eq!(dlx.step(2), SearchStep::Paused);
+        let checkpoint = dlx.checkpoint();
+
+        let path = std::env::temp_dir().join(format!(
+            "polyomino-tiling-checkpoint-{}.json",
+            std::process::id()
+        ));
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, checkpoint);
+        assert!(!loaded.is_exhausted());
+        assert_eq!(loaded.stats(), dlx.stats());
+
+        fs::write(&path, "{\"columns\": [").unwrap();
+        let error = Checkpoint::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,335 @@
+use std::io::{self, BufRead, Write};
+
+/// How a constraint that at most one of several literals is true is written as
+/// clauses, trading clauses for auxiliary variables.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AtMostOne {
+    /// One clause per pair of literals, no auxiliary variable
+    #[default]
+    Pairwise,
+    /// Sinz's sequential counter: about 3n clauses and n auxiliary variables
+    Sequential,
+    /// Each literal implies the binary digits of its index: n log n clauses and
+    /// log n auxiliary variables
+    Binary,
+}
+
+/// A formula in conjunctive normal form, as SAT solvers take it.
+///
+/// Variables are numbered from 1, and a literal is a variable or its negation.
+/// When the formula encodes an exact cover problem, see `DancingLinks::to_cnf`,
+/// variable `i + 1` tells whether row `i` is selected and the auxiliary variables
+/// come after the rows'.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cnf {
+    n_vars: usize,
+    n_rows: usize,
+    clauses: Vec<Vec<i32>>,
+    /// Lines written at the top of the DIMACS file
+    comments: Vec<String>,
+}
+
+impl Cnf {
+    /// Starts a formula over one variable per row, with no clause.
+    pub(crate) fn new(n_rows: usize) -> Self {
+        Self {
+            n_vars: n_rows,
+            n_rows,
+            clauses: Vec::new(),
+            comments: Vec::new(),
+        }
+    }
+
+    pub fn n_vars(&self) -> usize {
+        self.n_vars
+    }
+
+    pub fn clauses(&self) -> &[Vec<i32>] {
+        &self.clauses
+    }
+
+    /// Returns the variable telling whether row `row` is selected.
+    pub fn row_var(row: usize) -> i32 {
+        row as i32 + 1
+    }
+
+    pub(crate) fn new_var(&mut self) -> i32 {
+        self.n_vars += 1;
+        self.n_vars as i32
+    }
+
+    pub(crate) fn add_clause(&mut self, clause: Vec<i32>) {
+        self.clauses.push(clause);
+    }
+
+    pub(crate) fn add_comment(&mut self, comment: String) {
+        self.comments.push(comment);
+    }
+
+    /// Adds clauses that make at most one of `literals` true.
+    pub(crate) fn add_at_most_one(&mut self, literals: &[i32], encoding: AtMostOne) {
+        if literals.len() < 2 {
+            return;
+        }
+        match encoding {
+            AtMostOne::Pairwise => {
+                for (i, &first) in literals.iter().enumerate() {
+                    for &second in &literals[i + 1..] {
+                        self.add_clause(vec![-first, -second]);
+                    }
+                }
+            }
+            AtMostOne::Sequential => {
+                // counter[i] is true once one of the first i + 1 literals is
+                let n = literals.len();
+                let counter: Vec<i32> = (0..n - 1).map(|_| self.new_var()).collect();
+                self.add_clause(vec![-literals[0], counter[0]]);
+                for i in 1..n - 1 {
+                    self.add_clause(vec![-literals[i], counter[i]]);
+                    self.add_clause(vec![-counter[i - 1], counter[i]]);
+                    self.add_clause(vec![-literals[i], -counter[i - 1]]);
+                }
+                self.add_clause(vec![-literals[n - 1], -counter[n - 2]]);
+            }
+            AtMostOne::Binary => {
+                let n_bits = usize::BITS - (literals.len() - 1).leading_zeros();
+                let bits: Vec<i32> = (0..n_bits).map(|_| self.new_var()).collect();
+                for (i, &literal) in literals.iter().enumerate() {
+                    for (k, &bit) in bits.iter().enumerate() {
+                        let bit = if i >> k & 1 == 1 { bit } else { -bit };
+                        self.add_clause(vec![-literal, bit]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the formula in the DIMACS CNF format.
+    pub fn write_dimacs(&self, mut writer: impl Write) -> io::Result<()> {
+        for comment in &self.comments {
+            writeln!(writer, "c {}", comment)?;
+        }
+        writeln!(writer, "p cnf {} {}", self.n_vars, self.clauses.len())?;
+        for clause in &self.clauses {
+            for literal in clause {
+                write!(writer, "{} ", literal)?;
+            }
+            writeln!(writer, "0")?;
+        }
+        Ok(())
+    }
+
+    /// Returns the rows selected by `model`, an assignment given as true literals
+    /// such as the one returned by `parse_model`, in increasing order.
+    pub fn selected_rows(&self, model: &[i32]) -> Vec<usize> {
+        let mut rows: Vec<usize> = model
+            .iter()
+            .filter(|&&literal| literal > 0 && literal as usize <= self.n_rows)
+            .map(|&literal| literal as usize - 1)
+            .collect();
+        rows.sort_unstable();
+        rows.dedup();
+        rows
+    }
+}
+
+/// Reads the output of a SAT solver, returning the literals of its model, or `None`
+/// if it reported the formula unsatisfiable.
+///
+/// Both the competition format (`s SATISFIABLE` then `v` lines) and MiniSat's
+/// (`SAT` then a line of literals) are understood. Comment lines start with `c`.
+pub fn parse_model(reader: impl BufRead) -> io::Result<Option<Vec<i32>>> {
+    let mut model = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        let literals = match line.split_whitespace().next() {
+            None | Some("c") => continue,
+            Some("s") | Some("SAT") | Some("UNSAT") | Some("UNSATISFIABLE") => {
+                if line.contains("UNSAT") {
+                    return Ok(None);
+                }
+                continue;
+            }
+            Some("v") => &line[1..],
+            Some(_) => line,
+        };
+        for literal in literals.split_whitespace() {
+            let literal: i32 = literal.parse().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid literal {:?} in SAT solver output", literal),
+                )
+            })?;
+            if literal != 0 {
+                model.push(literal);
+            }
+        }
+    }
+    Ok(Some(model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dancing_links::DancingLinks;
+    use crate::error::DlxError;
+    use crate::rng::SplitMix64;
+
+    /// Whether the partial `assignment` of the variables, indexed from 0, can be
+    /// extended into a model of `clauses`.
+    fn is_satisfiable(clauses: &[Vec<i32>], assignment: &mut Vec<Option<bool>>) -> bool {
+        let value = |assignment: &[Option<bool>], literal: i32| {
+            assignment[literal.unsigned_abs() as usize - 1].map(|value| value == (literal > 0))
+        };
+        let unsatisfied = clauses
+            .iter()
+            .find(|clause| !clause.iter().any(|&l| value(assignment, l) == Some(true)));
+        let Some(clause) = unsatisfied else {
+            return true;
+        };
+        let Some(&literal) = clause.iter().find(|&&l| value(assignment, l).is_none()) else {
+            return false;
+        };
+        let var = literal.unsigned_abs() as usize - 1;
+        for guess in [true, false] {
+            assignment[var] = Some(guess);
+            if is_satisfiable(clauses, assignment) {
+                assignment[var] = None;
+                return true;
+            }
+        }
+        assignment[var] = None;
+        false
+    }
+
+    /// Returns every assignment of the first `n_shown` variables that can be
+    /// extended into a model of `cnf`, as the list of those that are true.
+    fn projected_models(cnf: &Cnf, n_shown: usize) -> Vec<Vec<usize>> {
+        let mut models = Vec::new();
+        for subset in 0usize..1 << n_shown {
+            let mut assignment = vec![None; cnf.n_vars()];
+            for (var, value) in assignment.iter_mut().enumerate().take(n_shown) {
+                *value = Some(subset >> var & 1 == 1);
+            }
+            if is_satisfiable(cnf.clauses(), &mut assignment) {
+                models.push((0..n_shown).filter(|&var| subset >> var & 1 == 1).collect());
+            }
+        }
+        models.sort();
+        models
+    }
+
+    #[test]
+    fn test_at_most_one_encodings() {
+        for n in 0..7 {
+            let mut expected: Vec<Vec<usize>> = (0..n).map(|v| vec![v]).collect();
+            expected.push(vec![]);
+            expected.sort();
+            for encoding in [
+                AtMostOne::Pairwise,
+                AtMostOne::Sequential,
+                AtMostOne::Binary,
+            ] {
+                let mut cnf = Cnf::new(n);
+                let literals: Vec<i32> = (0..n).map(Cnf::row_var).collect();
+                cnf.add_at_most_one(&literals, encoding);
+                assert_eq!(
+                    projected_models(&cnf, n),
+                    expected,
+                    "{:?}, n={}",
+                    encoding,
+                    n
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_exact_cover_models_match_solutions() {
+        let mut rng = SplitMix64::new(0x0123_4567_89ab_cdef);
+
+        for _ in 0..100 {
+            let n_primary = 1 + rng.below(4);
+            let n_secondary = rng.below(3);
+            let n_cols = n_primary + n_secondary;
+            let n_rows = 1 + rng.below(8);
+            let mut dlx = DancingLinks::new();
+            (0..n_primary).for_each(|_| dlx.append_column());
+            (0..n_secondary).for_each(|_| dlx.append_secondary_column());
+            for _ in 0..n_rows {
+                let row: Vec<usize> = (0..n_cols).map(|_| (rng.below(3) == 0) as usize).collect();
+                let colors: Vec<usize> = (0..n_cols)
+                    .map(|column| match column >= n_primary && row[column] == 1 {
+                        true => rng.below(3),
+                        false => 0,
+                    })
+                    .collect();
+                dlx.append_colored_row(&row, &colors).unwrap();
+            }
+            if rng.below(4) == 0 {
+                dlx.remove_row(rng.below(n_rows)).unwrap();
+            }
+            let mut solutions: Vec<Vec<usize>> = dlx
+                .solutions()
+                .map(|mut solution| {
+                    solution.sort();
+                    solution
+                })
+                .collect();
+            solutions.sort();
+
+            for encoding in [
+                AtMostOne::Pairwise,
+                AtMostOne::Sequential,
+                AtMostOne::Binary,
+            ] {
+                let cnf = dlx.to_cnf(encoding).unwrap();
+                assert_eq!(
+                    projected_models(&cnf, n_rows),
+                    solutions,
+                    "{:?}, matrix={:?}",
+                    encoding,
+                    dlx.to_vecs()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_exact_cover_multiplicity_unsupported() {
+        let mut dlx = DancingLinks::new();
+        dlx.append_column_with_multiplicity(0, 1).unwrap();
+        assert_eq!(
+            dlx.to_cnf(AtMostOne::Pairwise),
+            Err(DlxError::CnfUnsupported)
+        );
+    }
+
+    #[test]
+    fn test_write_dimacs_and_parse_model() {
+        let mut cnf = Cnf::new(3);
+        cnf.add_comment("three rows".to_string());
+        cnf.add_clause(vec![1, 2, 3]);
+        cnf.add_at_most_one(&[1, 2, 3], AtMostOne::Pairwise);
+        let mut text = Vec::new();
+        cnf.write_dimacs(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "c three rows\np cnf 3 4\n1 2 3 0\n-1 -2 0\n-1 -3 0\n-2 -3 0\n"
+        );
+
+        let output = "c solved\ns SATISFIABLE\nv -1 2\nv -3 0\n";
+        let model = parse_model(output.as_bytes()).unwrap().unwrap();
+        assert_eq!(model, vec![-1, 2, -3]);
+        assert_eq!(cnf.selected_rows(&model), vec![1]);
+        let output = "SAT\n1 -2 3 4 0\n";
+        let model = parse_model(output.as_bytes()).unwrap().unwrap();
+        assert_eq!(cnf.selected_rows(&model), vec![0, 2]);
+
+        assert_eq!(parse_model("s UNSATISFIABLE\n".as_bytes()).unwrap(), None);
+        assert_eq!(parse_model("UNSAT\n".as_bytes()).unwrap(), None);
+        let error = parse_model("v 1 x 0\n".as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
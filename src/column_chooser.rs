@@ -0,0 +1,131 @@
+use crate::dancing_links::{ActiveColumn, ActiveColumns};
+use crate::rng::SplitMix64;
+
+/// Strategy picking the primary column to branch on at each node of the search.
+///
+/// Implementations must be `Clone` so that a `DancingLinks` holding one can be
+/// cloned, each copy getting its own chooser.
+pub trait ColumnChooser: CloneColumnChooser + Send {
+    /// Returns the index of the column to branch on among the `columns` not yet
+    /// covered, which are never empty.
+    fn choose(&mut self, columns: ActiveColumns<'_>) -> usize;
+}
+
+/// Lets `Box<dyn ColumnChooser>` be cloned, implemented for every `Clone` chooser.
+pub trait CloneColumnChooser {
+    fn clone_box(&self) -> Box<dyn ColumnChooser>;
+}
+
+impl<T: ColumnChooser + Clone + 'static> CloneColumnChooser for T {
+    fn clone_box(&self) -> Box<dyn ColumnChooser> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn ColumnChooser> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Minimum remaining values: the column with the smallest branching degree, the
+/// first one on ties. This is what `DancingLinks` uses when no chooser is set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Mrv;
+
+impl ColumnChooser for Mrv {
+    fn choose(&mut self, columns: ActiveColumns<'_>) -> usize {
+        columns
+            .reduce(|best, column| {
+                if column.branching_degree < best.branching_degree {
+                    column
+                } else {
+                    best
+                }
+            })
+            .unwrap()
+            .index
+    }
+}
+
+/// The first column not yet covered, in the order the columns were appended.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FirstColumn;
+
+impl ColumnChooser for FirstColumn {
+    fn choose(&mut self, mut columns: ActiveColumns<'_>) -> usize {
+        columns.next().unwrap().index
+    }
+}
+
+/// Minimum remaining values, breaking ties with the column of highest priority,
+/// then with the first column.
+#[derive(Clone, Debug)]
+pub struct MrvWithPriority {
+    /// Priority of each column, columns past the end having priority 0
+    priorities: Vec<i32>,
+}
+
+impl MrvWithPriority {
+    pub fn new(priorities: Vec<i32>) -> Self {
+        Self { priorities }
+    }
+
+    fn priority(&self, column: &ActiveColumn) -> i32 {
+        self.priorities.get(column.index).copied().unwrap_or(0)
+    }
+}
+
+impl ColumnChooser for MrvWithPriority {
+    fn choose(&mut self, columns: ActiveColumns<'_>) -> usize {
+        columns
+            .reduce(|best, column| {
+                let key = (column.branching_degree, -self.priority(&column));
+                if key < (best.branching_degree, -self.priority(&best)) {
+                    column
+                } else {
+                    best
+                }
+            })
+            .unwrap()
+            .index
+    }
+}
+
+/// Minimum remaining values, breaking ties uniformly at random.
+///
+/// The choices only depend on the seed, so a search can be replayed.
+#[derive(Clone, Debug)]
+pub struct RandomizedMrv {
+    rng: SplitMix64,
+}
+
+impl RandomizedMrv {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: SplitMix64::new(seed),
+        }
+    }
+}
+
+impl ColumnChooser for RandomizedMrv {
+    fn choose(&mut self, columns: ActiveColumns<'_>) -> usize {
+        let mut best = usize::MAX;
+        let mut min_degree = usize::MAX;
+        let mut n_ties = 0;
+        for column in columns {
+            if column.branching_degree < min_degree {
+                min_degree = column.branching_degree;
+                best = column.index;
+                n_ties = 1;
+            } else if column.branching_degree == min_degree {
+                // Reservoir sampling keeps each tied column with equal probability
+                n_ties += 1;
+                if self.rng.below(n_ties) == 0 {
+                    best = column.index;
+                }
+            }
+        }
+        best
+    }
+}
@@ -1,549 +1,3777 @@
-use core::panic;
 use log::{debug, info};
-
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use web_time::{Duration, Instant};
+
+use crate::checkpoint::{Checkpoint, ColumnSpec};
+use crate::cnf::{AtMostOne, Cnf};
+use crate::column_chooser::{ColumnChooser, RandomizedMrv};
+use crate::error::DlxError;
+use crate::progress::{Branch, Progress, ProgressReporter};
+use crate::pruner::Pruner;
+use crate::rng::SplitMix64;
+use crate::search_stats::SearchStats;
+use crate::solve_options::{Limit, SolveOptions, SolveOutcome};
+use crate::trace::{SearchTrace, TraceEvent};
+use crate::zdd::{self, Zdd, ZddBuilder};
+
+/// Color of a node on a purified column whose color matches the purifying one.
+/// Such nodes are left in place while the column stays purified.
+const PURIFIED: u32 = u32::MAX;
+
+/// Column of the spacer nodes placed between rows
+const SPACER: u32 = u32::MAX;
+
+/// Slot of the list head of primary columns in `DancingLinks::columns`
+const ROOT: usize = 0;
+/// Slot of the list head of secondary columns, which may be covered at most once
+/// and are never chosen to branch on
+const SECONDARY_ROOT: usize = 1;
+/// Slot of column 0, the following columns come right after it
+const FIRST_COLUMN: usize = 2;
+
+/// Maximum number of steps taken between two checks of the `SolveOptions` limits
+pub(crate) const LIMIT_CHECK_INTERVAL: u64 = 1024;
+
+/// Exact cover matrix stored as flat arrays of nodes linked by `u32` indices.
+///
+/// Every column has a header node in `nodes` that closes its circular vertical
+/// list. The nodes of a row are contiguous and surrounded by spacer nodes, so
+/// rows need no horizontal links.
+#[derive(Clone)]
 pub struct DancingLinks {
-    root: *mut ColumnNode,
     n_rows: usize,
     n_cols: usize,
-    columns: Vec<*mut ColumnNode>,
-    nodes: Vec<*mut Node>,
+    /// Index in `nodes` of the spacer before each row, shared by an empty row with the
+    /// row after it
+    row_spacers: Vec<u32>,
+    /// The two list heads followed by one entry per column, see `FIRST_COLUMN`
+    columns: Vec<Column>,
+    nodes: Vec<Node>,
+    stack: Vec<Level>,
+    /// Rows taken out of the column branched on, for all levels of the stack
+    tweaks: Vec<u32>,
+    state: SearchState,
+    /// Statistics of the search in progress or of the last one
+    stats: SearchStats,
+    progress: Option<ProgressReporter>,
+    /// Strategy picking the column to branch on, minimum remaining values if `None`
+    chooser: Option<Box<dyn ColumnChooser>>,
+    /// Check cutting off branches after each row selected, if any
+    pruner: Option<Box<dyn Pruner>>,
+    /// Number of levels at the bottom of `stack` that the search must not backtrack
+    floor: usize,
+    /// Value of `updates` at which progress is reported next
+    next_progress_report: u64,
+    /// Labels of the columns and rows, cut short after the last one that has one
+    column_labels: Vec<Option<String>>,
+    row_labels: Vec<Option<String>>,
+    /// Whether each column and row was removed, cut short after the last removed one
+    removed_columns: Vec<bool>,
+    removed_rows: Vec<bool>,
+    /// Search tree recorded so far, if tracing
+    trace: Option<SearchTrace>,
 }
 
 impl DancingLinks {
     pub fn new() -> Self {
-        let mut root: Box<ColumnNode> = Box::new(ColumnNode::new(0));
-        root.left = &mut *root;
-        root.right = &mut *root;
-
         Self {
-            root: Box::into_raw(root),
             n_rows: 0,
             n_cols: 0,
-            columns: Vec::new(),
+            row_spacers: Vec::new(),
+            columns: vec![Column::root(ROOT), Column::root(SECONDARY_ROOT)],
             nodes: Vec::new(),
+            stack: Vec::new(),
+            tweaks: Vec::new(),
+            state: SearchState::Idle,
+            stats: SearchStats::default(),
+            progress: None,
+            chooser: None,
+            pruner: None,
+            floor: 0,
+            next_progress_report: u64::MAX,
+            column_labels: Vec::new(),
+            row_labels: Vec::new(),
+            removed_columns: Vec::new(),
+            removed_rows: Vec::new(),
+            trace: None,
         }
     }
 
-    pub fn from_vecs(matrix: &Vec<Vec<usize>>, n_cols: usize) -> Self {
-        if matrix.iter().any(|row| row.len() != n_cols) {
-            panic!(
-                "number of columns in each row must match. n_cols: {}",
-                n_cols
-            );
+    pub fn from_vecs(matrix: &Vec<Vec<usize>>, n_cols: usize) -> Result<Self, DlxError> {
+        let mut dl = DancingLinks::new();
+        for _ in 0..n_cols {
+            dl.append_column();
+        }
+
+        for row in matrix {
+            dl.append_row(row)?;
         }
+        Ok(dl)
+    }
 
+    /// Builds a matrix whose first `n_primary` columns must be covered exactly once
+    /// and whose last `n_secondary` columns may be covered at most once.
+    pub fn from_vecs_with_secondary(
+        matrix: &[Vec<usize>],
+        n_primary: usize,
+        n_secondary: usize,
+    ) -> Result<Self, DlxError> {
         let mut dl = DancingLinks::new();
-        for _ in 0..n_cols {
+        for _ in 0..n_primary {
             dl.append_column();
         }
+        for _ in 0..n_secondary {
+            dl.append_secondary_column();
+        }
 
         for row in matrix {
-            dl.append_row(row);
+            dl.append_row(row)?;
+        }
+        Ok(dl)
+    }
+
+    /// Builds a matrix from rows given as the increasing indices of the columns they
+    /// cover, see `append_sparse_row`. The first `n_primary` columns must be covered
+    /// exactly once and the last `n_secondary` columns at most once.
+    pub fn from_sparse_rows<R: AsRef<[usize]>>(
+        n_primary: usize,
+        n_secondary: usize,
+        rows: impl IntoIterator<Item = R>,
+    ) -> Result<Self, DlxError> {
+        let mut dl = DancingLinks::new();
+        for _ in 0..n_primary {
+            dl.append_column();
+        }
+        for _ in 0..n_secondary {
+            dl.append_secondary_column();
+        }
+
+        for row in rows {
+            dl.append_sparse_row(row.as_ref())?;
         }
-        dl
+        Ok(dl)
     }
 
     pub fn solve(&mut self) -> Option<Vec<usize>> {
-        self.solve_helper(vec![])
+        self.solutions().next()
     }
 
-    fn solve_helper(&mut self, mut solution: Vec<usize>) -> Option<Vec<usize>> {
-        info!("current candidate solution: {:?}", solution);
-        unsafe {
-            // 1. If no columns are left, already found a solution
-            if (*self.root).right == self.root {
-                return Some(solution);
-            }
+    /// Lazily enumerates every exact cover, in the order Algorithm X finds them.
+    ///
+    /// The matrix is restored to its original state when the iterator is dropped,
+    /// so callers can stop early (e.g. with `take(n)`) and search again later.
+    pub fn solutions(&mut self) -> Solutions<'_> {
+        self.reset_search();
+        Solutions { dlx: self }
+    }
 
-            // 2. Choose a column with the least number of 1s
-            let mut best_column = (*self.root).right;
-            let mut min_size = (*best_column).size;
-            let mut current = (*best_column).right;
+    /// Searches for an exact cover that includes every row of `rows`, see `force_rows`.
+    pub fn solve_with_prefix(&mut self, rows: &[usize]) -> Result<Option<Vec<usize>>, DlxError> {
+        Ok(self.solutions_with_prefix(rows)?.next())
+    }
 
-            while current != self.root {
-                if (*current).size < min_size {
-                    min_size = (*current).size;
-                    best_column = current;
-                }
-                current = (*current).right;
+    /// Lazily enumerates every exact cover that includes every row of `rows`, see
+    /// `force_rows`. The solutions start with the forced rows.
+    pub fn solutions_with_prefix(&mut self, rows: &[usize]) -> Result<Solutions<'_>, DlxError> {
+        self.force_rows(rows)?;
+        Ok(Solutions { dlx: self })
+    }
+
+    /// Restarts the search with `rows` selected, in this order, so that `step` only
+    /// looks for exact covers that include them, until `reset_search` is called.
+    ///
+    /// Each row is selected from its first primary column, as the search would if it
+    /// branched on that column and took that row first. Fails, leaving the search
+    /// reset, if a row covers no primary column or can not be selected along with the
+    /// rows before it: they cover one of its columns too many times, or give a column
+    /// another color.
+    pub fn force_rows(&mut self, rows: &[usize]) -> Result<(), DlxError> {
+        self.reset_search();
+        self.clear_stats();
+        for &row in rows {
+            if row >= self.n_rows {
+                self.reset_search();
+                return Err(DlxError::RowOutOfRange {
+                    row,
+                    n_rows: self.n_rows,
+                });
+            }
+            if self.is_row_removed(row) {
+                self.reset_search();
+                return Err(DlxError::RowRemoved { row });
+            }
+            let primary = self.row_nodes(row).find(|&node| {
+                let column = self.nodes[node].column as usize;
+                !self.columns[column].secondary
+            });
+            let Some(node) = primary else {
+                self.reset_search();
+                return Err(DlxError::RowWithoutPrimaryColumn { row });
+            };
+            if !self.select_forced_row(node) {
+                self.reset_search();
+                return Err(DlxError::ForcedRowConflict { row });
+            }
+            debug!("row {} forced", self.row_name(row));
+        }
+        self.floor = self.stack.len();
+        self.state = SearchState::Descending;
+        Ok(())
+    }
+
+    /// Pushes a level branching on the column of `node` with only its row, returning
+    /// `false` if the row can not be selected any more.
+    ///
+    /// Unlike `replay`, the rows before it in the column are not skipped, so deeper
+    /// levels can still select them when the column may be covered again.
+    fn select_forced_row(&mut self, node: usize) -> bool {
+        let column = self.nodes[node].column as usize;
+        if !self.is_active_primary(column) || !self.column_nodes(column).contains(&(node as u32)) {
+            return false;
+        }
+        self.push_level(column, 1);
+        self.stack.last_mut().unwrap().node = node as u32;
+        // Like `try_top_level`, take the row out of a column that stays in the search
+        if !self.columns[column].is_fully_used() {
+            if self.columns[column].bound != 0 {
+                self.links().hide(node);
             }
+            self.links().unlink_node(node);
+            self.stats.updates += 1;
+            self.tweaks.push(node as u32);
+        }
+        self.links().select_row(node);
+        true
+    }
 
-            debug!(
-                "best column chosen: col={}, size={}",
-                (*best_column).index,
-                (*best_column).size
-            );
+    /// Returns the nodes of row `row`, which must exist. Empty rows have no spacer of
+    /// their own and no node.
+    fn row_nodes(&self, row: usize) -> std::ops::Range<usize> {
+        let spacer = self.row_spacers[row] as usize;
+        let node = &self.nodes[spacer];
+        if node.color as usize != row || node.down == SPACER {
+            return 0..0;
+        }
+        spacer + 1..node.down as usize + 1
+    }
 
-            // 2. If there is columns with no 1s, no solution
-            if (*best_column).size == 0 {
-                return None;
-            }
-
-            // 4. Cover the column
-            debug!("covering column {}", (*best_column).index);
-            (*best_column).cover();
-
-            // 5. For each row in the column:
-            let head = (*best_column).head;
-            if !head.is_null() {
-                let mut row_node = head;
-                loop {
-                    // 5.1. Add the row index to the solution
-                    solution.push((*row_node).row_index);
-                    debug!(
-                        "row {} selected as part of the solution candidate",
-                        (*row_node).row_index
-                    );
-                    debug!("current candidate solution: {:?}", solution);
+    /// Returns the columns of row `row` with their colors, in increasing order.
+    ///
+    /// Purification changes colors during a search, so no search must be in progress,
+    /// see `idle`.
+    fn row_columns(&self, row: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.row_nodes(row).map(|node| {
+            let node = &self.nodes[node];
+            (node.column as usize - FIRST_COLUMN, node.color)
+        })
+    }
 
-                    // 5.2. Cover all columns that the row intersects with
-                    debug!(
-                        "covering columns that row {} intersects with",
-                        (*row_node).row_index
-                    );
-                    let mut col_node = (*row_node).right;
-                    while col_node != row_node {
-                        debug!("covering column {}", (*(*col_node).column).index,);
-                        (*(*col_node).column).cover();
-                        col_node = (*col_node).right;
-                    }
-                    debug!("all columns covered for row {}", (*row_node).row_index);
+    /// Returns column `column` as it was appended.
+    ///
+    /// Covering a column lowers its bound during a search, so no search must be in
+    /// progress, see `idle`.
+    fn column_spec(&self, column: usize) -> ColumnSpec {
+        let column = &self.columns[FIRST_COLUMN + column];
+        ColumnSpec {
+            secondary: column.secondary,
+            lo: (column.bound - column.slack) as usize,
+            hi: column.bound as usize,
+        }
+    }
 
-                    // 5.4. Recursively call solve_helper
-                    // 5.4.1. If a solution is found, return it
-                    if let Some(result) = self.solve_helper(solution.clone()) {
-                        return Some(result);
-                    }
+    /// Returns the matrix with no search in progress: this one, or a reset copy of it
+    /// when rows are selected.
+    fn idle(&self) -> Cow<'_, DancingLinks> {
+        if self.stack.is_empty() {
+            return Cow::Borrowed(self);
+        }
+        let mut matrix = self.clone();
+        matrix.reset_search();
+        Cow::Owned(matrix)
+    }
 
-                    // 5.4.2. If no solution, uncover the columns and try next row
-                    col_node = (*row_node).left;
-                    while col_node != row_node {
-                        (*(*col_node).column).uncover();
-                        col_node = (*col_node).left;
-                    }
+    /// Searches for an exact cover, giving up once one of the limits in `options` is hit.
+    ///
+    /// Limits are checked every few steps, so the search may take up to
+    /// `LIMIT_CHECK_INTERVAL` steps past a deadline or cancellation. The node limit
+    /// is never exceeded.
+    pub fn solve_with_options(&mut self, options: &SolveOptions) -> SolveOutcome<Vec<usize>> {
+        self.reset_search();
+        self.clear_stats();
+        let outcome = loop {
+            if let Some(limit) = options.reached_limit(self.stats.nodes) {
+                info!(
+                    "search stopped by {:?} after {} nodes",
+                    limit, self.stats.nodes
+                );
+                break SolveOutcome::LimitReached {
+                    limit,
+                    nodes: self.stats.nodes,
+                };
+            }
+            // Every step visits at most one node, so this budget stays within the limit
+            let max_steps = options.max_nodes.map_or(LIMIT_CHECK_INTERVAL, |max_nodes| {
+                (max_nodes - self.stats.nodes).min(LIMIT_CHECK_INTERVAL)
+            });
+            match self.step(max_steps) {
+                SearchStep::Solution(solution) => break SolveOutcome::Solved(solution),
+                SearchStep::Exhausted => break SolveOutcome::Unsolvable,
+                SearchStep::Paused => {}
+            }
+        };
+        self.reset_search();
+        outcome
+    }
 
-                    solution.pop();
+    /// Enumerates every exact cover, calling `on_solution` with each, and saves the
+    /// search to the checkpoint file at `path` every `interval` or so. A file left by
+    /// an earlier call is picked up where it stopped, so a run that was killed can be
+    /// started again with the same arguments.
+    ///
+    /// The checkpoint is also saved right before each solution is handed to
+    /// `on_solution`, so a resumed run never hands out a solution again, even one
+    /// whose `on_solution` call was cut short by the kill.
+    ///
+    /// When a limit of `options` is hit, the checkpoint is saved and the limit returned.
+    /// The node limit counts the nodes visited since the enumeration first started.
+    /// The file is kept once the search is exhausted, so that later calls return right
+    /// away.
+    pub fn enumerate_with_checkpoints(
+        &mut self,
+        path: impl AsRef<Path>,
+        interval: Duration,
+        options: &SolveOptions,
+        mut on_solution: impl FnMut(&[usize]),
+    ) -> io::Result<Option<Limit>> {
+        let path = path.as_ref();
+        if path.exists() {
+            self.restore(&Checkpoint::load(path)?)?;
+        } else {
+            self.reset_search();
+            self.clear_stats();
+        }
 
-                    row_node = (*row_node).down;
-                    if row_node == head {
-                        break;
-                    }
+        let mut last_save = Instant::now();
+        loop {
+            if let Some(limit) = options.reached_limit(self.stats.nodes) {
+                info!(
+                    "enumeration stopped by {:?} after {} nodes",
+                    limit, self.stats.nodes
+                );
+                self.checkpoint().save(path)?;
+                return Ok(Some(limit));
+            }
+            let max_steps = options.max_nodes.map_or(LIMIT_CHECK_INTERVAL, |max_nodes| {
+                (max_nodes - self.stats.nodes).min(LIMIT_CHECK_INTERVAL)
+            });
+            match self.step(max_steps) {
+                SearchStep::Solution(solution) => {
+                    self.checkpoint().save(path)?;
+                    last_save = Instant::now();
+                    on_solution(&solution);
+                    continue;
+                }
+                SearchStep::Exhausted => {
+                    self.checkpoint().save(path)?;
+                    return Ok(None);
                 }
+                SearchStep::Paused => {}
             }
+            if last_save.elapsed() >= interval {
+                self.checkpoint().save(path)?;
+                last_save = Instant::now();
+            }
+        }
+    }
 
-            // 6. When all rows are tried and yet no solution, uncover the column and return None
-            (*best_column).uncover();
-            None
+    /// Counts every exact cover without materializing any of them.
+    pub fn count_solutions(&mut self) -> u128 {
+        self.reset_search();
+        let mut count: u128 = 0;
+        while self.advance() {
+            count += 1;
         }
+        self.reset_search();
+        count
     }
 
-    pub fn append_column(&mut self) {
-        unsafe {
-            let old_rightmost = (*self.root).left;
-            let new_column: Box<ColumnNode> = Box::new(ColumnNode::new(self.n_cols));
-            let new_column_ptr: *mut ColumnNode = Box::into_raw(new_column);
-            (*old_rightmost).insert_right(new_column_ptr);
-            self.n_cols += 1;
-            self.columns.push(new_column_ptr);
+    /// Counts every exact cover like `count_solutions`, remembering the count of each
+    /// subproblem met so that it is searched only once, see `zdd`.
+    pub fn count_solutions_memoized(&mut self) -> Result<u128, DlxError> {
+        Ok(self.zdd()?.count())
+    }
+
+    /// Returns every exact cover as a zero-suppressed decision diagram over the rows.
+    ///
+    /// This is Knuth's Algorithm DXZ: the subproblem left at a node of the search tree
+    /// only depends on the columns still active, so the diagram built for it is cached
+    /// under that set and shared with every other node reaching it. On matrices with
+    /// many solutions made of interchangeable parts, the diagram and the search can be
+    /// exponentially smaller than the solutions.
+    ///
+    /// Only plain exact cover is supported: primary columns must be covered exactly
+    /// once and rows must not be colored.
+    pub fn zdd(&mut self) -> Result<Zdd, DlxError> {
+        self.reset_search();
+        let has_multiplicity = (0..self.n_cols).any(|i| {
+            let column = &self.columns[FIRST_COLUMN + i];
+            !self.is_column_removed(i)
+                && !column.secondary
+                && (column.bound != 1 || column.slack != 0)
+        });
+        let has_colors = (FIRST_COLUMN..self.columns.len()).any(|column| {
+            let nodes = self.column_nodes(column);
+            nodes
+                .iter()
+                .any(|&node| self.nodes[node as usize].color != 0)
+        });
+        if has_multiplicity || has_colors {
+            return Err(DlxError::MemoizationUnsupported);
         }
+
+        self.clear_stats();
+        let started = Instant::now();
+        let mut builder = ZddBuilder::new();
+        let mut cache = HashMap::new();
+        let root = self.memoized_search(&mut builder, &mut cache);
+        let zdd = builder.finish(root);
+        self.stats.solutions = u64::try_from(zdd.count()).unwrap_or(u64::MAX);
+        self.stats.elapsed += started.elapsed();
+        info!(
+            "memoized search done: {} subproblems, {} diagram nodes",
+            cache.len(),
+            zdd.nodes().len()
+        );
+        Ok(zdd)
     }
 
-    pub fn append_row(&mut self, row: &[usize]) {
-        if row.len() != self.n_cols {
-            panic!("Row length does not match number of columns");
+    /// Builds the diagram of the exact covers of the active columns, branching on a
+    /// column as `run_steps` does and leaving the matrix as it found it.
+    fn memoized_search(
+        &mut self,
+        builder: &mut ZddBuilder,
+        cache: &mut HashMap<Box<[u64]>, usize>,
+    ) -> usize {
+        let depth = self.stack.len();
+        if depth >= self.stats.nodes_per_depth.len() {
+            self.stats.nodes_per_depth.resize(depth + 1, 0);
         }
+        self.stats.nodes_per_depth[depth] += 1;
+        self.stats.nodes += 1;
 
-        let row_nodes = row
-            .iter()
-            .enumerate()
-            .filter(|&(_, &val)| val != 0)
-            .map(|(i, _)| {
-                let column = self.columns[i];
-                let new_node: Box<Node> = Box::new(Node::new(self.n_rows, column));
-                let new_node_ptr = Box::into_raw(new_node);
-                unsafe { (*column).append_node(new_node_ptr) };
-                new_node_ptr
-            })
-            .collect::<Vec<_>>();
+        if self.columns[ROOT].right as usize == ROOT {
+            return zdd::UNIT;
+        }
+        let signature = self.active_columns_signature();
+        if let Some(&node) = cache.get(&signature) {
+            return node;
+        }
 
-        // Link the nodes in the row
-        if !row_nodes.is_empty() {
-            let first_node = row_nodes[0];
-            unsafe {
-                (*first_node).left = first_node;
-                (*first_node).right = first_node;
+        let (best_column, branching_degree) = self.choose_column();
+        let mut branches = Vec::new();
+        if branching_degree != 0 {
+            self.push_level(best_column, branching_degree);
+            let mut entered = self.try_top_level();
+            while self.stack.len() > depth {
+                if entered {
+                    let row = row_of(&self.nodes, self.stack[depth].node as usize);
+                    branches.push((row, self.memoized_search(builder, cache)));
+                }
+                self.stats.backtracks += 1;
+                entered = self.next_branch();
             }
-            row_nodes.iter().skip(1).for_each(|&node| unsafe {
-                (*first_node).insert_left(node);
-            });
         }
 
-        // Push the row nodes to the main list
-        self.nodes.extend(row_nodes);
+        // The rows all cover the chosen column, so each solution holds at most one
+        let node = branches
+            .into_iter()
+            .rev()
+            .fold(zdd::EMPTY, |lo, (row, hi)| builder.node(row, lo, hi));
+        cache.insert(signature, node);
+        node
+    }
 
-        self.n_rows += 1;
+    /// Returns the set of primary and secondary columns still active, as a bitset of
+    /// their slots.
+    fn active_columns_signature(&self) -> Box<[u64]> {
+        let mut signature = vec![0; self.columns.len().div_ceil(64)];
+        for root in [ROOT, SECONDARY_ROOT] {
+            let mut current = self.columns[root].right as usize;
+            while current != root {
+                signature[current / 64] |= 1 << (current % 64);
+                current = self.columns[current].right as usize;
+            }
+        }
+        signature.into_boxed_slice()
     }
 
-    #[allow(dead_code)]
-    pub fn to_vecs(&self) -> Vec<Vec<usize>> {
-        let mut matrix = vec![vec![0; self.n_cols]; self.n_rows];
+    /// Calls `callback` with the state of the search every `interval` updates or so,
+    /// an update being the removal of a node from its column. An interval of 0 is
+    /// taken as 1.
+    ///
+    /// The callback is shared by clones of this matrix.
+    pub fn set_progress_callback(
+        &mut self,
+        interval: u64,
+        callback: impl FnMut(&Progress) + Send + 'static,
+    ) {
+        self.set_progress_reporter(Some(ProgressReporter::new(interval, callback)));
+    }
 
-        unsafe {
-            // Iterate through each column
-            let mut current_col = (*self.root).right;
-            while current_col != self.root {
-                let col_index = (*current_col).index;
-
-                // Iterate through all nodes in this column
-                let head = (*current_col).head;
-                if !head.is_null() {
-                    let mut current_node = head;
-                    loop {
-                        let row_index = (*current_node).row_index;
-                        matrix[row_index][col_index] = 1;
-
-                        current_node = (*current_node).down;
-                        if current_node == head {
-                            break;
-                        }
-                    }
-                }
+    pub fn clear_progress_callback(&mut self) {
+        self.set_progress_reporter(None);
+    }
 
-                current_col = (*current_col).right;
-            }
-        }
+    pub(crate) fn set_progress_reporter(&mut self, reporter: Option<ProgressReporter>) {
+        self.next_progress_report = match &reporter {
+            Some(reporter) => self.stats.updates.saturating_add(reporter.interval),
+            None => u64::MAX,
+        };
+        self.progress = reporter;
+    }
 
-        matrix
+    /// Records the search tree explored from now on, down to depth `max_depth`, see
+    /// `SearchTrace`. Like the statistics, the trace covers the search in progress
+    /// or the last one.
+    ///
+    /// Depths count every level of the search, including rows forced with
+    /// `force_rows`. Deeper nodes are only counted.
+    pub fn enable_trace(&mut self, max_depth: usize) {
+        self.trace = Some(SearchTrace::new(max_depth));
     }
-}
 
-impl Drop for DancingLinks {
-    fn drop(&mut self) {
-        debug!("Dropping DancingLinks using vectors");
+    /// Stops recording the search tree and returns the trace recorded so far, if
+    /// any, along with the names of the columns and rows for `SearchTrace::write_dot`.
+    pub fn take_trace(&mut self) -> Option<SearchTrace> {
+        let mut trace = self.trace.take()?;
+        let column_names = (0..self.n_cols).map(|i| self.column_name(i)).collect();
+        let row_names = (0..self.n_rows)
+            .map(|row| {
+                self.row_label(row)
+                    .map_or_else(|| row.to_string(), str::to_string)
+            })
+            .collect();
+        trace.set_names(column_names, row_names);
+        Some(trace)
+    }
 
-        // Drop all nodes using the vector (avoid traversing corrupted linked lists)
-        debug!("Dropping {} nodes", self.nodes.len());
-        for &node in &self.nodes {
-            unsafe {
-                debug!("Dropping node at row {}", (*node).row_index);
-                drop(Box::from_raw(node));
-            }
-        }
+    /// Sets the strategy picking the column to branch on at each node of the search.
+    ///
+    /// By default the column with the fewest ways to branch on is chosen, the first
+    /// one on ties (see `column_chooser::Mrv`).
+    pub fn set_column_chooser(&mut self, chooser: Box<dyn ColumnChooser>) {
+        self.chooser = Some(chooser);
+    }
+
+    /// Sets the check run after each row selected by the search, which gives up on the
+    /// branch when it fails, see `Pruner`.
+    ///
+    /// Like the column chooser, the pruner is not captured by checkpoints. The
+    /// memoized search of `zdd` does not run it.
+    pub fn set_pruner(&mut self, pruner: Box<dyn Pruner>) {
+        self.pruner = Some(pruner);
+    }
+
+    /// Makes the search explore rows and break ties between columns in an order
+    /// drawn from `seed`, so that different seeds tend to find different solutions
+    /// first while the same seed always replays the same search.
+    ///
+    /// This shuffles the rows (see `shuffle_rows`) and replaces the column chooser
+    /// with `RandomizedMrv`.
+    pub fn randomize(&mut self, seed: u64) {
+        let mut rng = SplitMix64::new(seed);
+        self.shuffle_rows(rng.next_u64());
+        self.set_column_chooser(Box::new(RandomizedMrv::new(rng.next_u64())));
+    }
 
-        // Drop all columns using the vector
-        debug!("Dropping {} columns", self.columns.len());
-        for &column in &self.columns {
-            unsafe {
-                debug!("Dropping column {}", (*column).index);
-                drop(Box::from_raw(column));
+    /// Shuffles the order in which the rows of each column are tried, from `seed`.
+    ///
+    /// Row indices are unchanged, only the search order is. Any search in progress
+    /// is reset first.
+    pub fn shuffle_rows(&mut self, seed: u64) {
+        self.reset_search();
+        let mut rng = SplitMix64::new(seed);
+        for column in FIRST_COLUMN..self.columns.len() {
+            let mut column_nodes = self.column_nodes(column);
+            // Fisher-Yates shuffle
+            for i in (1..column_nodes.len()).rev() {
+                column_nodes.swap(i, rng.below(i + 1));
             }
+            self.relink_column_in_order(column, &column_nodes);
         }
+    }
 
-        // Drop root
-        debug!("Dropping root");
-        unsafe {
-            drop(Box::from_raw(self.root));
+    /// Returns the nodes linked in the column at `column`, from top to bottom.
+    fn column_nodes(&self, column: usize) -> Vec<u32> {
+        let header = self.columns[column].header;
+        let mut column_nodes = Vec::with_capacity(self.columns[column].len as usize);
+        let mut current = self.nodes[header as usize].down;
+        while current != header {
+            column_nodes.push(current);
+            current = self.nodes[current as usize].down;
         }
+        column_nodes
     }
-}
 
-struct ColumnNode {
-    index: usize,
-    size: usize,
-    left: *mut ColumnNode,
-    right: *mut ColumnNode,
-    head: *mut Node,
-}
+    /// Relinks the column at `column` so that the search tries its nodes in the
+    /// order of `column_nodes`, which must be the nodes it holds.
+    fn relink_column_in_order(&mut self, column: usize, column_nodes: &[u32]) {
+        let header = self.columns[column].header;
+        let mut previous = header;
+        for &node in column_nodes {
+            self.nodes[previous as usize].down = node;
+            self.nodes[node as usize].up = previous;
+            previous = node;
+        }
+        self.nodes[previous as usize].down = header;
+        self.nodes[header as usize].up = previous;
+    }
 
-impl ColumnNode {
-    fn new(index: usize) -> Self {
-        Self {
-            index,
-            size: 0,
-            left: std::ptr::null_mut(),
-            right: std::ptr::null_mut(),
-            head: std::ptr::null_mut(),
+    /// Lists the nodes of the search tree at `depth`, along with the solutions found
+    /// above it, in the order the search visits them.
+    ///
+    /// Searching below each of them with `enter` covers the whole search tree, which
+    /// lets independent copies of the matrix share the work.
+    pub(crate) fn split(&mut self, depth: usize) -> Vec<SearchPath> {
+        if depth == 0 {
+            return vec![SearchPath(Vec::new())];
+        }
+        self.reset_search();
+        let mut frontier = Vec::new();
+        loop {
+            self.run(1);
+            match self.state {
+                SearchState::Descending if self.stack.len() == depth => {
+                    frontier.push(self.current_path());
+                    self.state = SearchState::Backtracking;
+                }
+                SearchState::AtSolution => frontier.push(self.current_path()),
+                SearchState::Exhausted => break,
+                _ => {}
+            }
         }
+        self.reset_search();
+        frontier
     }
 
-    fn insert_right(&mut self, new_column: *mut ColumnNode) {
-        unsafe {
-            if new_column.is_null() {
-                panic!("New column is null");
+    /// Restarts the search at the node reached by `path`, which must come from this
+    /// matrix or a copy of it. The search then never backtracks above that node.
+    pub(crate) fn enter(&mut self, path: &SearchPath) {
+        self.reset_search();
+        self.clear_stats();
+        let entered = self.replay(path);
+        debug_assert!(entered, "search path does not lead to a node");
+        self.floor = self.stack.len();
+        self.state = SearchState::Descending;
+    }
+
+    /// Branches along `path` from the node of the search tree the stack is at, as the
+    /// search would have, returning `false` if it does not lead to a node of the tree.
+    ///
+    /// The search must be paused or reset, and is left paused at the end of the path.
+    fn replay(&mut self, path: &SearchPath) -> bool {
+        for &(column, node) in &path.0 {
+            let depth = self.stack.len();
+            let column = column as usize;
+            if !self.is_active_primary(column) {
+                return false;
+            }
+            self.push_level(column, self.columns[column].branching_degree());
+            // A level is popped once all of its branches were tried
+            let mut entered = self.try_top_level();
+            while self.stack.len() > depth && self.stack[depth].node != node {
+                entered = self.next_branch();
+            }
+            if self.stack.len() == depth || !entered {
+                return false;
             }
-            let old_right = self.right;
-            if old_right.is_null() {
-                panic!("Right column is null");
+        }
+        true
+    }
+
+    /// Whether `column` is a primary column still to be covered.
+    fn is_active_primary(&self, column: usize) -> bool {
+        let mut current = self.columns[ROOT].right as usize;
+        while current != ROOT {
+            if current == column {
+                return true;
             }
-            self.right = new_column;
-            (*new_column).left = self;
-            (*new_column).right = old_right;
-            (*old_right).left = new_column;
+            current = self.columns[current].right as usize;
+        }
+        false
+    }
+
+    fn current_path(&self) -> SearchPath {
+        SearchPath(
+            self.stack
+                .iter()
+                .map(|level| (level.column, level.node))
+                .collect(),
+        )
+    }
+
+    /// Captures the matrix and the position of the search in progress, from which
+    /// `restore` or `from_checkpoint` pick the search up later, possibly in another
+    /// process.
+    ///
+    /// The column chooser is not captured. A restored search branches on the same
+    /// columns down to the saved position, and whatever chooser it has below it, so
+    /// it still finds each remaining solution exactly once.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let matrix = self.idle();
+        let rows = (0..matrix.n_rows)
+            .map(|row| matrix.row_columns(row).collect())
+            .collect();
+        let (columns, row_order) = (0..matrix.n_cols)
+            .map(|column| {
+                let order = matrix
+                    .column_nodes(FIRST_COLUMN + column)
+                    .iter()
+                    .map(|&node| row_of(&matrix.nodes, node as usize))
+                    .collect();
+                (matrix.column_spec(column), order)
+            })
+            .unzip();
+
+        let path = self
+            .stack
+            .iter()
+            .map(|level| {
+                let column = level.column as usize;
+                let row = (level.node != self.columns[column].header)
+                    .then(|| row_of(&self.nodes, level.node as usize));
+                (column - FIRST_COLUMN, row)
+            })
+            .collect();
+
+        Checkpoint {
+            columns,
+            rows,
+            column_labels: self.column_labels.clone(),
+            row_labels: self.row_labels.clone(),
+            row_order,
+            removed_columns: flagged(&self.removed_columns),
+            removed_rows: flagged(&self.removed_rows),
+            path,
+            floor: self.floor,
+            state: self.state,
+            stats: self.stats.clone(),
         }
     }
 
-    fn append_node(&mut self, new_node: *mut Node) {
-        unsafe {
-            if self.head.is_null() {
-                self.head = new_node;
-                (*new_node).up = new_node;
-                (*new_node).down = new_node;
+    /// Builds the matrix saved in `checkpoint` and moves its search to the saved
+    /// position, see `restore`.
+    pub fn from_checkpoint(checkpoint: &Checkpoint) -> io::Result<Self> {
+        let n_cols = checkpoint.columns.len();
+        let mut dlx = DancingLinks::new();
+        for spec in &checkpoint.columns {
+            if spec.secondary {
+                dlx.append_secondary_column();
             } else {
-                let head = self.head;
-                (*head).insert_up(new_node);
-            }
-            self.size += 1;
-        }
-    }
-
-    fn cover(&mut self) {
-        unsafe {
-            // 1. Unlink the column node from the neighboring columns
-            self.unlink();
-
-            // 2. For each node in this column
-            let mut current = self.head;
-            if !current.is_null() {
-                debug!("head is not null, starting to cover column {}", self.index);
-                loop {
-                    // 3. For each node in the same row as current
-                    debug!("unlinking row {}", (*current).row_index);
-                    let mut row_node = (*current).right;
-                    while row_node != current {
-                        // Unlink the node vertically from its column
-                        debug!("unlinking node vertically: {:?}", (*row_node).get_loc());
-                        (*row_node).unlink_vertically();
-                        row_node = (*row_node).right;
-                        debug!("next to unlink vertically: {:?}", (*row_node).get_loc());
-                    }
-                    debug!("completed unlinking row {}", (*current).row_index);
-                    current = (*current).down;
-                    if current == self.head {
-                        break;
-                    }
-                    debug!("next row to unlink: {}", (*current).row_index);
+                dlx.append_column_with_multiplicity(spec.lo, spec.hi)
+                    .map_err(|error| invalid_checkpoint(&error.to_string()))?;
+            }
+        }
+        for entries in &checkpoint.rows {
+            let increasing = entries.windows(2).all(|pair| pair[0].0 < pair[1].0);
+            if !increasing || entries.last().is_some_and(|&(column, _)| column >= n_cols) {
+                return Err(invalid_checkpoint("invalid row entry"));
+            }
+            for &(column, color) in entries {
+                if color != 0 && (color == PURIFIED || !checkpoint.columns[column].secondary) {
+                    return Err(invalid_checkpoint("invalid row color"));
                 }
-                debug!("finished covering column {}", self.index);
-            } else {
-                debug!("head is null, nothing to cover in column {}", self.index);
             }
+            dlx.append_row_entries(entries.iter().copied());
+        }
+        let n_rows = checkpoint.rows.len();
+        if checkpoint.column_labels.len() > n_cols || checkpoint.row_labels.len() > n_rows {
+            return Err(invalid_checkpoint("invalid labels"));
+        }
+        dlx.column_labels = checkpoint.column_labels.clone();
+        dlx.row_labels = checkpoint.row_labels.clone();
+        for &row in &checkpoint.removed_rows {
+            dlx.remove_row(row)
+                .map_err(|error| invalid_checkpoint(&error.to_string()))?;
         }
+        for &column in &checkpoint.removed_columns {
+            dlx.remove_column(column)
+                .map_err(|error| invalid_checkpoint(&error.to_string()))?;
+        }
+        dlx.restore(checkpoint)?;
+        Ok(dlx)
     }
 
-    fn uncover(&mut self) {
-        unsafe {
-            // Do everything in reverse order of cover
-            let mut current = self.head;
-            if !current.is_null() {
-                // Go backwards through the column
-                current = (*current).up;
-                loop {
-                    // For each node in the same row as current (in reverse)
-                    let mut row_node = (*current).left;
-                    while row_node != current {
-                        // Relink the node vertically to its column
-                        (*row_node).relink_vertically();
-                        row_node = (*row_node).left;
-                    }
-                    if current == self.head {
-                        break;
-                    }
-                    current = (*current).up;
-                }
+    /// Moves the search to the position saved in `checkpoint`, so that `step` goes on
+    /// from there, with the statistics of the search so far.
+    ///
+    /// The checkpoint must have been taken on a matrix with the same columns and rows,
+    /// otherwise an `InvalidData` error is returned and the search is reset. Rows are
+    /// then tried in the order saved in the checkpoint, like after `shuffle_rows`.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> io::Result<()> {
+        let current = self.checkpoint();
+        self.reset_search();
+        if current.columns != checkpoint.columns
+            || current.rows != checkpoint.rows
+            || current.removed_columns != checkpoint.removed_columns
+            || current.removed_rows != checkpoint.removed_rows
+        {
+            return Err(invalid_checkpoint(
+                "checkpoint was taken on a different matrix",
+            ));
+        }
+
+        for (i, order) in checkpoint.row_order.iter().enumerate() {
+            let column = FIRST_COLUMN + i;
+            // Nodes are laid out in row order, whatever order the column is linked in
+            let mut nodes_by_row: Vec<(usize, u32)> = self
+                .column_nodes(column)
+                .into_iter()
+                .map(|node| (row_of(&self.nodes, node as usize), node))
+                .collect();
+            nodes_by_row.sort_unstable();
+            let mut sorted_order = order.clone();
+            sorted_order.sort_unstable();
+            if !sorted_order
+                .iter()
+                .eq(nodes_by_row.iter().map(|(row, _)| row))
+            {
+                return Err(invalid_checkpoint("invalid row order"));
             }
+            let column_nodes: Vec<u32> = order
+                .iter()
+                .map(|row| {
+                    let index = nodes_by_row.binary_search_by_key(row, |&(row, _)| row);
+                    nodes_by_row[index.unwrap()].1
+                })
+                .collect();
+            self.relink_column_in_order(column, &column_nodes);
+        }
 
-            // Relink the column node to neighboring columns
-            self.relink();
+        let mut path = Vec::with_capacity(checkpoint.path.len());
+        for &(column, row) in &checkpoint.path {
+            if column >= self.n_cols {
+                return Err(invalid_checkpoint("invalid search path"));
+            }
+            let column = FIRST_COLUMN + column;
+            let node = match row {
+                None => Some(self.columns[column].header),
+                Some(row) => self
+                    .column_nodes(column)
+                    .into_iter()
+                    .find(|&node| row_of(&self.nodes, node as usize) == row),
+            };
+            let Some(node) = node else {
+                return Err(invalid_checkpoint("invalid search path"));
+            };
+            path.push((column as u32, node));
+        }
+        let floor = checkpoint.floor;
+        let resumable = match checkpoint.state {
+            SearchState::Idle => path.is_empty(),
+            SearchState::Exhausted => path.len() == floor,
+            _ => floor <= path.len(),
+        };
+        let restored = resumable
+            && path[..floor].iter().all(|&(column, node)| {
+                node != self.columns[column as usize].header
+                    && self.select_forced_row(node as usize)
+            })
+            && self.replay(&SearchPath(path[floor..].to_vec()));
+        if !restored {
+            self.reset_search();
+            return Err(invalid_checkpoint("invalid search path"));
         }
+
+        self.floor = floor;
+        self.state = checkpoint.state;
+        self.stats = checkpoint.stats.clone();
+        self.next_progress_report = self.progress.as_ref().map_or(u64::MAX, |reporter| {
+            self.stats.updates.saturating_add(reporter.interval)
+        });
+        info!(
+            "search restored at depth {} after {} nodes",
+            self.stack.len(),
+            self.stats.nodes
+        );
+        Ok(())
+    }
+
+    /// Returns the statistics of the search in progress, or of the last one.
+    ///
+    /// They are kept until the next search starts, so they can be read after `solve`,
+    /// `count_solutions` or a `solutions` iterator is done.
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
     }
 
-    fn unlink(&mut self) {
-        unsafe {
-            let left = self.left;
-            let right = self.right;
-            (*left).right = right;
-            (*right).left = left;
+    /// Runs the search for at most `max_steps` steps, picking up where the last call
+    /// stopped.
+    ///
+    /// A step is one visit of a node of the search tree, either descending into it or
+    /// backtracking out of it, so callers such as UI loops can bound the work done per
+    /// call. Once `Exhausted` is returned, `reset_search` starts the search over.
+    pub fn step(&mut self, max_steps: u64) -> SearchStep {
+        self.run(max_steps);
+        match self.state {
+            SearchState::AtSolution => SearchStep::Solution(self.current_rows()),
+            SearchState::Exhausted => SearchStep::Exhausted,
+            _ => SearchStep::Paused,
         }
     }
 
-    fn relink(&mut self) {
-        unsafe {
-            let left = self.left;
-            let right = self.right;
-            (*right).left = self;
-            (*left).right = self;
+    /// Advances the search to the next exact cover and returns its row indices.
+    pub(crate) fn next_solution(&mut self) -> Option<Vec<usize>> {
+        if self.advance() {
+            Some(self.current_rows())
+        } else {
+            None
         }
     }
-}
 
-struct Node {
-    row_index: usize,
-    column: *mut ColumnNode,
-    up: *mut Node,
-    down: *mut Node,
-    left: *mut Node,
-    right: *mut Node,
-}
+    /// Advances the search to the next exact cover, returning `false` once exhausted.
+    fn advance(&mut self) -> bool {
+        self.run(u64::MAX);
+        self.state == SearchState::AtSolution
+    }
 
-impl Node {
-    fn new(row_index: usize, column: *mut ColumnNode) -> Self {
-        Self {
-            row_index,
-            column,
-            up: std::ptr::null_mut(),
-            down: std::ptr::null_mut(),
-            left: std::ptr::null_mut(),
-            right: std::ptr::null_mut(),
+    /// Runs the search until it reaches an exact cover, is exhausted, or has taken
+    /// `max_steps` steps, leaving the outcome in `self.state`.
+    fn run(&mut self, max_steps: u64) {
+        if self.state == SearchState::Idle {
+            self.clear_stats();
+        }
+        let started = Instant::now();
+        self.run_steps(max_steps);
+        self.stats.elapsed += started.elapsed();
+    }
+
+    /// Clears the statistics, and with them the progress schedule, for a new search.
+    fn clear_stats(&mut self) {
+        self.stats = SearchStats::default();
+        self.next_progress_report = self.progress.as_ref().map_or(u64::MAX, |r| r.interval);
+        if let Some(trace) = &mut self.trace {
+            trace.clear();
         }
     }
-    fn insert_down(&mut self, new_node: *mut Node) {
-        unsafe {
-            if new_node.is_null() {
-                panic!("New node is null");
+
+    /// The search loop of `run`.
+    ///
+    /// This is Knuth's Algorithm M, which reduces to Algorithm X (with colors) when
+    /// every primary column must be covered exactly once. The search state lives in
+    /// `self.stack`, one level per branching decision, so a call resumes right where
+    /// the previous one stopped.
+    fn run_steps(&mut self, max_steps: u64) {
+        let mut descend = match self.state {
+            SearchState::Idle | SearchState::Descending => true,
+            SearchState::AtSolution | SearchState::Backtracking => false,
+            SearchState::Exhausted => return,
+        };
+
+        let mut steps = 0;
+        loop {
+            if steps == max_steps {
+                self.state = if descend {
+                    SearchState::Descending
+                } else {
+                    SearchState::Backtracking
+                };
+                return;
+            }
+            steps += 1;
+            if self.stats.updates >= self.next_progress_report {
+                self.report_progress();
             }
-            let old_down = self.down;
-            if old_down.is_null() {
-                panic!("Down node is null");
+
+            if descend {
+                let depth = self.stack.len();
+                if depth >= self.stats.nodes_per_depth.len() {
+                    self.stats.nodes_per_depth.resize(depth + 1, 0);
+                }
+                self.stats.nodes_per_depth[depth] += 1;
+                self.stats.nodes += 1;
+                let trace_id = self.trace_enter(depth);
+
+                // 1. If no columns are left, already found a solution
+                if self.columns[ROOT].right as usize == ROOT {
+                    info!(
+                        "solution found: {:?}",
+                        self.solution_labels(&self.current_rows())
+                    );
+                    if let Some(id) = trace_id {
+                        let rows = self.current_rows();
+                        self.record_trace(TraceEvent::Solution { id, rows });
+                    }
+                    self.stats.solutions += 1;
+                    self.state = SearchState::AtSolution;
+                    return;
+                }
+
+                // 2. Let the pruner give up on the row just selected
+                if depth > self.floor && !self.pruner_keeps_top_level() {
+                    self.stats.pruned += 1;
+                    if let Some(id) = trace_id {
+                        self.record_trace(TraceEvent::Pruned { id });
+                    }
+                    descend = false;
+                    continue;
+                }
+
+                // 3. Choose the column with the fewest ways to branch on
+                let (best_column, branching_degree) = self.choose_column();
+                let column = &self.columns[best_column];
+                debug!(
+                    "best column chosen: col={}, size={}, bound={}, slack={}",
+                    self.column_name(best_column - FIRST_COLUMN),
+                    column.len,
+                    column.bound,
+                    column.slack
+                );
+
+                if let Some(id) = trace_id {
+                    let column = best_column - FIRST_COLUMN;
+                    self.record_trace(match branching_degree {
+                        0 => TraceEvent::DeadEnd { id, column },
+                        degree => TraceEvent::Choose { id, column, degree },
+                    });
+                }
+
+                // 4. If the column can not be satisfied, this branch has no solution
+                if branching_degree == 0 {
+                    descend = false;
+                    continue;
+                }
+
+                // 5. Branch on the column
+                self.push_level(best_column, branching_degree);
+                descend = self.try_top_level();
+            } else {
+                // 6. Backtrack, unless back at the levels fixed before the search
+                if self.stack.len() == self.floor {
+                    if let Some(trace) = &mut self.trace {
+                        trace.finish();
+                    }
+                    self.state = SearchState::Exhausted;
+                    return;
+                }
+                self.stats.backtracks += 1;
+                descend = self.next_branch();
             }
-            self.down = new_node;
-            (*new_node).up = self;
-            (*new_node).down = old_down;
-            (*old_down).up = new_node;
         }
     }
 
-    fn insert_right(&mut self, new_node: *mut Node) {
-        unsafe {
-            if new_node.is_null() {
-                panic!("New node is null");
+    /// Records entering a node of the search tree at `depth`, returning its id in the
+    /// trace, or `None` when not tracing or deeper than the trace goes.
+    fn trace_enter(&mut self, depth: usize) -> Option<usize> {
+        let trace = self.trace.as_mut()?;
+        // The row selected at the level above, none at the root
+        let row = match self.stack.last() {
+            Some(level)
+                if depth > self.floor
+                    && level.node != self.columns[level.column as usize].header =>
+            {
+                Some(row_of(&self.nodes, level.node as usize))
             }
-            let old_right = self.right;
-            if old_right.is_null() {
-                panic!("Right node is null");
-            }
-            self.right = new_node;
-            (*new_node).left = self;
-            (*new_node).right = old_right;
-            (*old_right).left = new_node;
+            _ => None,
+        };
+        trace.enter(depth, row)
+    }
+
+    /// Whether the pruner, if any, lets the search go on below the row selected at the
+    /// deepest level. Leaving a column out is not checked.
+    fn pruner_keeps_top_level(&mut self) -> bool {
+        let Some(pruner) = &mut self.pruner else {
+            return true;
+        };
+        let level = self.stack.last().unwrap();
+        if level.node == self.columns[level.column as usize].header {
+            return true;
         }
+        let row = row_of(&self.nodes, level.node as usize);
+        let columns = ActiveColumns {
+            columns: &self.columns,
+            current: self.columns[ROOT].right as usize,
+        };
+        pruner.keep(row, columns)
     }
 
-    fn insert_up(&mut self, new_node: *mut Node) {
-        unsafe {
-            (*self.up).insert_down(new_node);
+    fn record_trace(&mut self, event: TraceEvent) {
+        if let Some(trace) = &mut self.trace {
+            trace.record(event);
         }
     }
 
-    fn insert_left(&mut self, new_node: *mut Node) {
-        unsafe {
-            (*self.left).insert_right(new_node);
+    /// Uses up one of the column's remaining covers, covering it on the last, and
+    /// pushes a level branching on it, positioned on its first row.
+    fn push_level(&mut self, column: usize, n_branches: usize) {
+        let column_ref = &mut self.columns[column];
+        column_ref.bound -= 1;
+        let header = column_ref.header;
+        if column_ref.bound == 0 {
+            debug!(
+                "covering column {}",
+                self.column_name(column - FIRST_COLUMN)
+            );
+            self.links().cover(column);
         }
+        self.stack.push(Level {
+            column: column as u32,
+            node: self.nodes[header as usize].down,
+            tweaks_start: self.tweaks.len(),
+            branch: 0,
+            n_branches: n_branches as u32,
+        });
     }
 
-    fn unlink_vertically(&mut self) {
-        unsafe {
-            let up = self.up;
-            let down = self.down;
-            let column = self.column;
-            (*up).down = down;
-            (*down).up = up;
+    /// Deselects the row at the deepest level and tries the next one, returning
+    /// whether the search can go deeper.
+    fn next_branch(&mut self) -> bool {
+        let level = *self.stack.last().unwrap();
+        let column = level.column as usize;
+        let header = self.columns[column].header;
+        if level.node == header {
+            // The branch that stopped using the column is done as well
+            if self.columns[column].bound != 0 {
+                self.links().relink_column(column);
+            }
+            self.restore_top_level();
+            return false;
+        }
+        self.links().deselect_row(level.node as usize);
+        // Tweaked rows are gone from the column, so the next one is first in it
+        let previous = if self.columns[column].is_fully_used() {
+            level.node
+        } else {
+            header
+        };
+        let level = self.stack.last_mut().unwrap();
+        level.node = self.nodes[previous as usize].down;
+        level.branch += 1;
+        self.try_top_level()
+    }
 
-            // If I am the head now, make down node the new head
-            // but down node is me, i.e. I am the only node in this column, make head null
-            if (*column).head == self {
-                if down == self {
-                    (*column).head = std::ptr::null_mut();
-                } else {
-                    (*column).head = down;
-                }
+    /// Tries the row at the deepest level, returning whether the search can go deeper.
+    ///
+    /// The column's header stands for the branch where the column is not covered any
+    /// more. When no branch is left at this level, it is restored and popped.
+    fn try_top_level(&mut self) -> bool {
+        let level = *self.stack.last().unwrap();
+        let column = &self.columns[level.column as usize];
+        let node = level.node as usize;
+        let is_header = level.node == column.header;
+
+        if column.is_fully_used() {
+            if is_header {
+                self.restore_top_level();
+                return false;
             }
-            if (*column).size == 0 {
-                panic!("Attempting to unlink from empty column {}", (*column).index);
+            self.links().select_row(node);
+            debug!(
+                "current candidate solution: {:?}",
+                self.solution_labels(&self.current_rows())
+            );
+            return true;
+        }
+
+        // The remaining rows can not make up the covers still required
+        if column.len as i64 <= column.bound as i64 - column.slack as i64 {
+            self.restore_top_level();
+            return false;
+        }
+
+        if is_header {
+            if column.bound != 0 {
+                debug!(
+                    "leaving column {} out",
+                    self.column_name(level.column as usize - FIRST_COLUMN)
+                );
+                self.links().unlink_column(level.column as usize);
             }
-            (*column).size -= 1;
+            return true;
         }
+
+        // Take the row out of the column so that deeper levels can not choose it again
+        if column.bound != 0 {
+            self.links().hide(node);
+        }
+        self.links().unlink_node(node);
+        self.stats.updates += 1;
+        self.tweaks.push(node as u32);
+
+        self.links().select_row(node);
+        debug!(
+            "current candidate solution: {:?}",
+            self.solution_labels(&self.current_rows())
+        );
+        true
     }
 
-    fn relink_vertically(&mut self) {
-        unsafe {
-            let up = self.up;
-            let down = self.down;
-            (*up).down = self;
-            (*down).up = self;
-            if (*self.column).head.is_null() {
-                (*self.column).head = self;
+    /// Reverts the deepest level to the state before its column was chosen, then pops it.
+    fn restore_top_level(&mut self) {
+        let level = self.stack.pop().unwrap();
+        let column = level.column as usize;
+        if self.columns[column].is_fully_used() {
+            self.links().uncover(column);
+        } else {
+            while self.tweaks.len() > level.tweaks_start {
+                let node = self.tweaks.pop().unwrap() as usize;
+                self.links().relink_node(node);
+                if self.columns[column].bound != 0 {
+                    self.links().unhide(node);
+                }
+            }
+            if self.columns[column].bound == 0 {
+                self.links().uncover(column);
             }
-            (*self.column).size += 1;
         }
+        self.columns[column].bound += 1;
     }
 
-    fn get_loc(&self) -> (usize, Option<usize>) {
-        unsafe {
-            let column_index = if self.column.is_null() {
-                None
+    /// Undoes every level of an in-progress search, leaving the matrix as built.
+    pub fn reset_search(&mut self) {
+        while let Some(&level) = self.stack.last() {
+            let column = level.column as usize;
+            if level.node == self.columns[column].header {
+                if self.columns[column].bound != 0 {
+                    self.links().relink_column(column);
+                }
             } else {
-                Some((*self.column).index)
-            };
-            let row_index = self.row_index;
-            (row_index, column_index)
+                self.links().deselect_row(level.node as usize);
+            }
+            self.restore_top_level();
         }
+        self.state = SearchState::Idle;
+        self.floor = 0;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn current_rows(&self) -> Vec<usize> {
+        self.stack
+            .iter()
+            .filter(|level| level.node != self.columns[level.column as usize].header)
+            .map(|level| row_of(&self.nodes, level.node as usize))
+            .collect()
+    }
 
-    #[test]
-    fn test_dancing_links_simple() {
-        let _ = env_logger::try_init();
-        let mut dlx = DancingLinks::from_vecs(&vec![vec![1, 0], vec![0, 1]], 2);
-        assert_eq!(dlx.n_rows, 2);
-        assert_eq!(dlx.n_cols, 2);
-        assert_eq!(dlx.to_vecs(), vec![vec![1, 0], vec![0, 1]]);
-        assert_eq!(dlx.solve(), Some(vec![0, 1]));
+    fn report_progress(&mut self) {
+        let Some(reporter) = &self.progress else {
+            return;
+        };
+        let progress = Progress {
+            depth: self.stack.len(),
+            nodes: self.stats.nodes,
+            updates: self.stats.updates,
+            solutions: self.stats.solutions,
+            branches: self
+                .stack
+                .iter()
+                .map(|level| Branch {
+                    index: level.branch as usize,
+                    count: level.n_branches as usize,
+                })
+                .collect(),
+        };
+        reporter.report(&progress);
+        self.next_progress_report = self.stats.updates.saturating_add(reporter.interval);
     }
 
-    #[test]
-    fn test_dancing_links_complex() {
-        let _ = env_logger::try_init();
-        let mut dlx = DancingLinks::from_vecs(
-            &vec![
-                vec![1, 0, 0, 1, 0, 0, 1],
-                vec![1, 0, 0, 1, 0, 0, 0],
-                vec![0, 0, 0, 1, 1, 0, 1],
-                vec![0, 0, 1, 0, 1, 1, 0],
-                vec![0, 1, 0, 0, 0, 1, 1],
-                vec![0, 1, 0, 0, 0, 0, 1],
-            ],
-            7,
-        );
+    fn links(&mut self) -> Links<'_> {
+        Links {
+            nodes: &mut self.nodes,
+            columns: &mut self.columns,
+            updates: &mut self.stats.updates,
+        }
+    }
 
-        assert_eq!(dlx.n_rows, 6);
-        assert_eq!(dlx.n_cols, 7);
+    /// Returns the primary column with the smallest branching degree, along with it.
+    ///
+    /// The branching degree is the number of rows that can still be chosen first for
+    /// the column's remaining required covers, plus one when the column may be left
+    /// with no more covers. For a column covered exactly once it is just its size.
+    fn choose_column(&mut self) -> (usize, usize) {
+        if let Some(chooser) = &mut self.chooser {
+            let columns = ActiveColumns {
+                columns: &self.columns,
+                current: self.columns[ROOT].right as usize,
+            };
+            let best_column = FIRST_COLUMN + chooser.choose(columns);
+            let column = &self.columns[best_column];
+            debug_assert!(
+                !column.secondary && column.header != u32::MAX,
+                "chooser picked an invalid column"
+            );
+            return (best_column, column.branching_degree());
+        }
 
-        let solution = dlx.solve();
-        assert!(solution.is_some());
-        let mut sol = solution.unwrap();
-        sol.sort(); // Sort for consistent comparison
-        assert_eq!(sol, vec![1, 3, 5]);
+        let mut best_column = self.columns[ROOT].right as usize;
+        let mut min_degree = self.columns[best_column].branching_degree();
+        let mut current = self.columns[best_column].right as usize;
+
+        while current != ROOT {
+            let degree = self.columns[current].branching_degree();
+            if degree < min_degree {
+                min_degree = degree;
+                best_column = current;
+            }
+            current = self.columns[current].right as usize;
+        }
+        (best_column, min_degree)
     }
 
-    #[test]
-    fn test_dancing_links_no_solution() {
-        let _ = env_logger::try_init();
-        let mut dlx = DancingLinks::from_vecs(&vec![vec![1, 0], vec![1, 0]], 2);
+    pub fn append_column(&mut self) {
+        self.append_column_to(ROOT);
+    }
 
-        assert_eq!(dlx.solve(), None);
+    /// Appends a column that must be covered at least `lo` and at most `hi` times.
+    pub fn append_column_with_multiplicity(
+        &mut self,
+        lo: usize,
+        hi: usize,
+    ) -> Result<(), DlxError> {
+        if hi == 0 || lo > hi {
+            return Err(DlxError::InvalidMultiplicity { lo, hi });
+        }
+        self.append_column_to(ROOT);
+        let column = self.columns.last_mut().unwrap();
+        column.bound = hi as u32;
+        column.slack = (hi - lo) as u32;
+        Ok(())
     }
 
-    #[test]
-    fn test_dancing_links_single_row() {
-        // Matrix with single row covering all columns:
+    /// Appends a column that may be covered zero or one times.
+    pub fn append_secondary_column(&mut self) {
+        self.append_column_to(SECONDARY_ROOT);
+    }
+
+    fn append_column_to(&mut self, root: usize) {
+        self.reset_search();
+        let slot = self.columns.len() as u32;
+        let header = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            up: header,
+            down: header,
+            column: slot,
+            color: 0,
+        });
+
+        // Insert the new column at the end of the list
+        let old_rightmost = self.columns[root].left;
+        self.columns.push(Column {
+            left: old_rightmost,
+            right: root as u32,
+            header,
+            len: 0,
+            secondary: root == SECONDARY_ROOT,
+            bound: 1,
+            slack: 0,
+            color: 0,
+        });
+        self.columns[old_rightmost as usize].right = slot;
+        self.columns[root].left = slot;
+        self.n_cols += 1;
+    }
+
+    pub fn append_row(&mut self, row: &[usize]) -> Result<(), DlxError> {
+        self.append_row_with_colors(row, |_| 0)
+    }
+
+    /// Appends a row whose entries on secondary columns carry a color.
+    ///
+    /// `colors[i]` is the color of the entry in column `i`, with 0 meaning uncolored.
+    /// Any number of selected rows may share a colored column as long as they all
+    /// give it the same color; an uncolored entry still claims the column exclusively.
+    pub fn append_colored_row(&mut self, row: &[usize], colors: &[usize]) -> Result<(), DlxError> {
+        if colors.len() != self.n_cols {
+            return Err(DlxError::ColorsLength {
+                expected: self.n_cols,
+                actual: colors.len(),
+            });
+        }
+        for (i, &color) in colors.iter().enumerate() {
+            if color == 0 {
+                continue;
+            }
+            if row.get(i) == Some(&0) {
+                return Err(DlxError::ColorOnUncoveredColumn { column: i });
+            }
+            if color >= PURIFIED as usize {
+                return Err(DlxError::ColorTooLarge { color });
+            }
+            if !self.columns[FIRST_COLUMN + i].secondary {
+                return Err(DlxError::ColorOnPrimaryColumn { column: i });
+            }
+        }
+        self.append_row_with_colors(row, |i| colors[i] as u32)
+    }
+
+    fn append_row_with_colors(
+        &mut self,
+        row: &[usize],
+        color_of: impl Fn(usize) -> u32,
+    ) -> Result<(), DlxError> {
+        if row.len() != self.n_cols {
+            return Err(DlxError::RowLength {
+                expected: self.n_cols,
+                actual: row.len(),
+            });
+        }
+        if let Some(column) = (0..row.len()).find(|&i| row[i] != 0 && self.is_column_removed(i)) {
+            return Err(DlxError::ColumnRemoved { column });
+        }
+        let entries = row.iter().enumerate().filter(|&(_, &val)| val != 0);
+        self.append_row_entries(entries.map(|(i, _)| (i, color_of(i))));
+        Ok(())
+    }
+
+    /// Appends a row given by the indices of the columns it covers, in increasing
+    /// order.
+    ///
+    /// Building a matrix this way takes time and memory proportional to its number of
+    /// ones rather than to its size.
+    pub fn append_sparse_row(&mut self, columns: &[usize]) -> Result<(), DlxError> {
+        if columns.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(DlxError::ColumnsNotIncreasing);
+        }
+        if let Some(&column) = columns.last().filter(|&&column| column >= self.n_cols) {
+            return Err(DlxError::ColumnOutOfRange {
+                column,
+                n_cols: self.n_cols,
+            });
+        }
+        if let Some(&column) = columns.iter().find(|&&c| self.is_column_removed(c)) {
+            return Err(DlxError::ColumnRemoved { column });
+        }
+        self.append_row_entries(columns.iter().map(|&column| (column, 0)));
+        Ok(())
+    }
+
+    /// Appends a row from its column indices and colors, which must be valid.
+    fn append_row_entries(&mut self, entries: impl Iterator<Item = (usize, u32)>) {
+        self.reset_search();
+        // The spacer before the row links to its last node and keeps its index
+        if self.nodes.last().is_none_or(|node| node.column != SPACER) {
+            self.nodes.push(Node {
+                up: SPACER,
+                down: SPACER,
+                column: SPACER,
+                color: 0,
+            });
+        }
+        let spacer = self.nodes.len() - 1;
+        self.nodes[spacer].color = self.n_rows as u32;
+        self.row_spacers.push(spacer as u32);
+
+        let first = self.nodes.len() as u32;
+        for (i, color) in entries {
+            // Append the node at the bottom of its column
+            let column = FIRST_COLUMN + i;
+            let header = self.columns[column].header;
+            let node = self.nodes.len() as u32;
+            let old_bottom = self.nodes[header as usize].up;
+            self.nodes.push(Node {
+                up: old_bottom,
+                down: header,
+                column: column as u32,
+                color,
+            });
+            self.nodes[old_bottom as usize].down = node;
+            self.nodes[header as usize].up = node;
+            self.columns[column].len += 1;
+        }
+
+        // The spacer after the row links back to its first node
+        let last = self.nodes.len() as u32 - 1;
+        if last >= first {
+            self.nodes[spacer].down = last;
+            self.nodes.push(Node {
+                up: first,
+                down: SPACER,
+                column: SPACER,
+                color: 0,
+            });
+        }
+
+        self.n_rows += 1;
+    }
+
+    /// Removes row `row` from the matrix, so that no search selects it any more.
+    ///
+    /// The row only stops being linked into its columns, which takes time
+    /// proportional to its length: the other rows keep their indices, and the removed
+    /// one still counts in `to_vecs` and in the indices of rows appended later, as a
+    /// row with no columns. Any search in progress is reset first.
+    pub fn remove_row(&mut self, row: usize) -> Result<(), DlxError> {
+        if row >= self.n_rows {
+            return Err(DlxError::RowOutOfRange {
+                row,
+                n_rows: self.n_rows,
+            });
+        }
+        if self.is_row_removed(row) {
+            return Err(DlxError::RowRemoved { row });
+        }
+        self.reset_search();
+        for node in self.row_nodes(row) {
+            self.links().unlink_node(node);
+        }
+        set_flag(&mut self.removed_rows, row);
+        Ok(())
+    }
+
+    /// Removes column `column` from the matrix, which must have no rows left: it is
+    /// then no longer to be covered, and rows appended later can not cover it.
+    ///
+    /// Like with `remove_row`, the other columns keep their indices.
+    pub fn remove_column(&mut self, column: usize) -> Result<(), DlxError> {
+        if column >= self.n_cols {
+            return Err(DlxError::ColumnOutOfRange {
+                column,
+                n_cols: self.n_cols,
+            });
+        }
+        if self.is_column_removed(column) {
+            return Err(DlxError::ColumnRemoved { column });
+        }
+        self.reset_search();
+        if self.columns[FIRST_COLUMN + column].len != 0 {
+            return Err(DlxError::ColumnNotEmpty { column });
+        }
+        self.links().unlink_column(FIRST_COLUMN + column);
+        set_flag(&mut self.removed_columns, column);
+        Ok(())
+    }
+
+    pub fn is_row_removed(&self, row: usize) -> bool {
+        self.removed_rows.get(row) == Some(&true)
+    }
+
+    pub fn is_column_removed(&self, column: usize) -> bool {
+        self.removed_columns.get(column) == Some(&true)
+    }
+
+    /// Names column `column`, e.g. `r2c3`. Labels show up in logs and exports in
+    /// place of column indices.
+    pub fn set_column_label(
+        &mut self,
+        column: usize,
+        label: impl Into<String>,
+    ) -> Result<(), DlxError> {
+        if column >= self.n_cols {
+            return Err(DlxError::ColumnOutOfRange {
+                column,
+                n_cols: self.n_cols,
+            });
+        }
+        set_label(&mut self.column_labels, column, label.into());
+        Ok(())
+    }
+
+    /// Names row `row`, e.g. `F@r1c2/rot90`. Labels show up in logs and exports in
+    /// place of row indices, and `solution_labels` turns solutions into them.
+    pub fn set_row_label(&mut self, row: usize, label: impl Into<String>) -> Result<(), DlxError> {
+        if row >= self.n_rows {
+            return Err(DlxError::RowOutOfRange {
+                row,
+                n_rows: self.n_rows,
+            });
+        }
+        set_label(&mut self.row_labels, row, label.into());
+        Ok(())
+    }
+
+    pub fn column_label(&self, column: usize) -> Option<&str> {
+        self.column_labels.get(column)?.as_deref()
+    }
+
+    pub fn row_label(&self, row: usize) -> Option<&str> {
+        self.row_labels.get(row)?.as_deref()
+    }
+
+    /// Returns the label of each row of `solution`, or its index when it has none.
+    pub fn solution_labels(&self, solution: &[usize]) -> Vec<String> {
+        solution.iter().map(|&row| self.row_name(row)).collect()
+    }
+
+    /// Returns the label of column `column`, or its index when it has none.
+    pub(crate) fn column_name(&self, column: usize) -> String {
+        self.column_label(column)
+            .map_or_else(|| column.to_string(), str::to_string)
+    }
+
+    /// Returns the label of row `row`, or its index when it has none.
+    pub(crate) fn row_name(&self, row: usize) -> String {
+        self.row_label(row)
+            .map_or_else(|| row.to_string(), str::to_string)
+    }
+
+    #[allow(dead_code)]
+    pub fn to_vecs(&self) -> Vec<Vec<usize>> {
+        let mut matrix = vec![vec![0; self.n_cols]; self.n_rows];
+
+        // Iterate through each column, primary and secondary
+        for (col_index, column) in self.columns[FIRST_COLUMN..].iter().enumerate() {
+            // Iterate through all nodes in this column
+            let header = column.header as usize;
+            let mut current_node = self.nodes[header].down as usize;
+            while current_node != header {
+                let row_index = row_of(&self.nodes, current_node);
+                matrix[row_index][col_index] = 1;
+                current_node = self.nodes[current_node].down as usize;
+            }
+        }
+
+        matrix
+    }
+
+    /// Encodes the exact cover problem as a formula whose models are its solutions,
+    /// with a variable per row, see `Cnf`.
+    ///
+    /// Each primary column gets a clause requiring one of its rows and an at-most-one
+    /// constraint written with `encoding`. A colored secondary column gets a variable
+    /// per color, implied by the rows with that color, and at most one of them or of
+    /// its uncolored rows is selected. Rows with no primary column, which the search
+    /// never selects, and removed rows are ruled out.
+    ///
+    /// Primary columns must be covered exactly once.
+    pub fn to_cnf(&self, encoding: AtMostOne) -> Result<Cnf, DlxError> {
+        let matrix = self.idle();
+        if (0..self.n_cols).any(|column| {
+            let spec = matrix.column_spec(column);
+            !self.is_column_removed(column) && !spec.secondary && (spec.lo, spec.hi) != (1, 1)
+        }) {
+            return Err(DlxError::CnfUnsupported);
+        }
+
+        let mut cnf = Cnf::new(self.n_rows);
+        cnf.add_comment(format!(
+            "exact cover of {} columns by {} rows, variable i is row i - 1",
+            self.n_cols, self.n_rows
+        ));
+        for row in 0..self.n_rows {
+            if let Some(label) = self.row_label(row) {
+                cnf.add_comment(format!("{} {}", Cnf::row_var(row), label));
+            }
+        }
+
+        let mut rows_of_column = vec![Vec::new(); self.n_cols];
+        for row in 0..self.n_rows {
+            if self.is_row_removed(row) {
+                cnf.add_clause(vec![-Cnf::row_var(row)]);
+                continue;
+            }
+            let mut has_primary = false;
+            for (column, color) in matrix.row_columns(row) {
+                rows_of_column[column].push((row, color));
+                has_primary |= !matrix.column_spec(column).secondary;
+            }
+            if !has_primary {
+                cnf.add_clause(vec![-Cnf::row_var(row)]);
+            }
+        }
+        for (column, rows) in rows_of_column.iter().enumerate() {
+            if self.is_column_removed(column) {
+                continue;
+            }
+            let mut literals = Vec::new();
+            let mut color_vars = HashMap::new();
+            for &(row, color) in rows {
+                if color == 0 {
+                    literals.push(Cnf::row_var(row));
+                } else {
+                    let color_var = *color_vars.entry(color).or_insert_with(|| {
+                        let var = cnf.new_var();
+                        literals.push(var);
+                        var
+                    });
+                    cnf.add_clause(vec![-Cnf::row_var(row), color_var]);
+                }
+            }
+            if !matrix.column_spec(column).secondary {
+                cnf.add_clause(literals.clone());
+            }
+            cnf.add_at_most_one(&literals, encoding);
+        }
+        Ok(cnf)
+    }
+
+    /// Reads a matrix in the text format of Knuth's DLX programs.
+    ///
+    /// The first line lists the names of the primary items (columns), then `|` and
+    /// the names of the secondary ones. A primary item may be prefixed with its
+    /// multiplicity, `lo:hi|name` or `hi|name` for `lo == hi`. Every following line
+    /// is an option (row) listing the names of its items, with `name:color` giving a
+    /// secondary item a color. Lines starting with `|` are comments.
+    ///
+    /// Items become columns in the order they are listed, primary ones first, and are
+    /// labeled with their name. Colors are numbered from 1 in order of appearance.
+    pub fn read_dlx(reader: impl BufRead) -> io::Result<Self> {
+        let mut dlx = DancingLinks::new();
+        let mut column_of = HashMap::new();
+        let mut color_of = HashMap::new();
+        let mut has_items = false;
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('|') {
+                continue;
+            }
+            let invalid = |message: String| invalid_dlx_line(line_number, &message);
+
+            if !has_items {
+                has_items = true;
+                let mut secondary = false;
+                for token in line.split_whitespace() {
+                    let (multiplicity, name) = match token.split_once('|') {
+                        Some(("", name)) => {
+                            secondary = true;
+                            (None, name)
+                        }
+                        Some((_, _)) if secondary => {
+                            return Err(invalid(format!(
+                                "secondary item {} has a multiplicity",
+                                token
+                            )));
+                        }
+                        Some((multiplicity, name)) => (Some(multiplicity), name),
+                        None => (None, token),
+                    };
+                    if name.is_empty() {
+                        continue;
+                    }
+                    if name.contains([':', '|']) {
+                        return Err(invalid(format!("invalid item name {}", name)));
+                    }
+                    if column_of.insert(name.to_string(), dlx.n_cols).is_some() {
+                        return Err(invalid(format!("duplicate item name {}", name)));
+                    }
+                    if secondary {
+                        dlx.append_secondary_column();
+                    } else if let Some(multiplicity) = multiplicity {
+                        let (lo, hi) = parse_multiplicity(multiplicity).ok_or_else(|| {
+                            invalid(format!("invalid multiplicity {}", multiplicity))
+                        })?;
+                        dlx.append_column_with_multiplicity(lo, hi)
+                            .map_err(|error| invalid(error.to_string()))?;
+                    } else {
+                        dlx.append_column();
+                    }
+                    dlx.set_column_label(dlx.n_cols - 1, name)
+                        .map_err(|error| invalid(error.to_string()))?;
+                }
+                continue;
+            }
+
+            let mut entries = Vec::new();
+            for token in line.split_whitespace() {
+                let (name, color) = match token.split_once(':') {
+                    Some((name, color)) => (name, Some(color)),
+                    None => (token, None),
+                };
+                let &column = column_of
+                    .get(name)
+                    .ok_or_else(|| invalid(format!("unknown item {}", name)))?;
+                let color = match color {
+                    None => 0,
+                    Some(_) if !dlx.columns[FIRST_COLUMN + column].secondary => {
+                        return Err(invalid(
+                            DlxError::ColorOnPrimaryColumn { column }.to_string(),
+                        ));
+                    }
+                    Some(color) => {
+                        let next_color = color_of.len() as u32 + 1;
+                        *color_of.entry(color.to_string()).or_insert(next_color)
+                    }
+                };
+                entries.push((column, color));
+            }
+            entries.sort_unstable();
+            if entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+                return Err(invalid("an item appears twice in the option".to_string()));
+            }
+            dlx.append_row_entries(entries.into_iter());
+        }
+        Ok(dlx)
+    }
+
+    /// Writes the matrix in the text format of Knuth's DLX programs, see `read_dlx`.
+    ///
+    /// Columns are named after their labels, or their indices when they have none,
+    /// and colors are written as numbers. The label of a row is written in a comment
+    /// line right before it. Knuth's programs also limit names to 8 characters.
+    ///
+    /// Removed rows and columns are left out, so the rows read back come in the same
+    /// order but with the removed ones skipped.
+    ///
+    /// Fails with an `InvalidInput` error if two columns have the same name, a name
+    /// is empty or holds whitespace, `:` or `|`, or a row is empty.
+    pub fn write_dlx(&self, mut writer: impl Write) -> io::Result<()> {
+        let matrix = self.idle();
+        let names: Vec<String> = (0..self.n_cols).map(|i| self.column_name(i)).collect();
+        let mut seen = HashSet::new();
+        for (column, name) in names.iter().enumerate() {
+            if self.is_column_removed(column) {
+                continue;
+            }
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ':' || c == '|')
+            {
+                return Err(invalid_dlx_input(format!("invalid column name {:?}", name)));
+            }
+            if !seen.insert(name) {
+                return Err(invalid_dlx_input(format!(
+                    "duplicate column name {:?}",
+                    name
+                )));
+            }
+        }
+
+        let live_rows: Vec<usize> = (0..self.n_rows)
+            .filter(|&row| !self.is_row_removed(row))
+            .collect();
+        if let Some(&row) = live_rows
+            .iter()
+            .find(|&&row| matrix.row_nodes(row).is_empty())
+        {
+            return Err(invalid_dlx_input(format!("row {} is empty", row)));
+        }
+
+        let (secondary, primary): (Vec<usize>, Vec<usize>) = (0..self.n_cols)
+            .filter(|&column| !self.is_column_removed(column))
+            .partition(|&column| matrix.column_spec(column).secondary);
+        writeln!(
+            writer,
+            "| {} primary and {} secondary items, {} options",
+            primary.len(),
+            secondary.len(),
+            live_rows.len()
+        )?;
+        let mut items = Vec::with_capacity(primary.len() + secondary.len() + 1);
+        for &column in &primary {
+            let spec = matrix.column_spec(column);
+            let name = &names[column];
+            items.push(match (spec.lo, spec.hi) {
+                (1, 1) => name.clone(),
+                (lo, hi) if lo == hi => format!("{}|{}", hi, name),
+                (lo, hi) => format!("{}:{}|{}", lo, hi, name),
+            });
+        }
+        if !secondary.is_empty() {
+            items.push("|".to_string());
+            items.extend(secondary.iter().map(|&column| names[column].clone()));
+        }
+        writeln!(writer, "{}", items.join(" "))?;
+
+        for row in live_rows {
+            if let Some(label) = self.row_label(row) {
+                writeln!(writer, "| {}", label)?;
+            }
+            let option: Vec<String> = matrix
+                .row_columns(row)
+                .map(|(column, color)| match color {
+                    0 => names[column].clone(),
+                    color => format!("{}:{}", names[column], color),
+                })
+                .collect();
+            writeln!(writer, "{}", option.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Mutable view of the links of a `DancingLinks`, on which the search updates them.
+///
+/// Working on slices rather than through the vectors lets the compiler keep their
+/// bounds in registers, which makes the hot loops of cover/uncover much faster.
+struct Links<'a> {
+    nodes: &'a mut [Node],
+    columns: &'a mut [Column],
+    updates: &'a mut u64,
+}
+
+impl Links<'_> {
+    /// Returns the node to the right of this one in its row, wrapping around.
+    fn next_in_row(&self, node: usize) -> usize {
+        let next = node + 1;
+        if self.nodes[next].column == SPACER {
+            self.nodes[next].up as usize
+        } else {
+            next
+        }
+    }
+
+    /// Returns the node to the left of this one in its row, wrapping around.
+    fn previous_in_row(&self, node: usize) -> usize {
+        let previous = node - 1;
+        if self.nodes[previous].column == SPACER {
+            self.nodes[previous].down as usize
+        } else {
+            previous
+        }
+    }
+
+    /// Commits all columns that the row intersects with, except the one it was chosen from.
+    ///
+    /// Uncolored columns are covered. Colored secondary columns are purified instead,
+    /// so rows agreeing on the color can still be selected later.
+    fn select_row(&mut self, node: usize) {
+        debug!(
+            "row {} selected as part of the solution candidate",
+            row_of(self.nodes, node)
+        );
+        let mut other = self.next_in_row(node);
+        while other != node {
+            let Node { column, color, .. } = self.nodes[other];
+            let column = column as usize;
+            match color {
+                0 if !self.columns[column].secondary => {
+                    self.columns[column].bound -= 1;
+                    if self.columns[column].bound == 0 {
+                        debug!("covering column {}", column - FIRST_COLUMN);
+                        self.cover(column);
+                    }
+                }
+                0 => {
+                    debug!("covering column {}", column - FIRST_COLUMN);
+                    self.cover(column);
+                }
+                PURIFIED => {}
+                _ => {
+                    debug!("purifying column {}", column - FIRST_COLUMN);
+                    self.purify(other);
+                }
+            }
+            other = self.next_in_row(other);
+        }
+    }
+
+    /// Reverts `select_row` by uncommitting the columns in reverse order.
+    fn deselect_row(&mut self, node: usize) {
+        let mut other = self.previous_in_row(node);
+        while other != node {
+            let Node { column, color, .. } = self.nodes[other];
+            let column = column as usize;
+            match color {
+                0 if !self.columns[column].secondary => {
+                    self.columns[column].bound += 1;
+                    if self.columns[column].bound == 1 {
+                        self.uncover(column);
+                    }
+                }
+                0 => self.uncover(column),
+                PURIFIED => {}
+                _ => self.unpurify(other),
+            }
+            other = self.previous_in_row(other);
+        }
+    }
+
+    /// Unlinks the column from its list and hides every row in it.
+    fn cover(&mut self, column: usize) {
+        self.unlink_column(column);
+        let header = self.columns[column].header as usize;
+        let mut current = self.nodes[header].down as usize;
+        while current != header {
+            self.hide(current);
+            current = self.nodes[current].down as usize;
+        }
+    }
+
+    /// Reverts `cover`, going through the column in reverse order.
+    fn uncover(&mut self, column: usize) {
+        let header = self.columns[column].header as usize;
+        let mut current = self.nodes[header].up as usize;
+        while current != header {
+            self.unhide(current);
+            current = self.nodes[current].up as usize;
+        }
+        self.relink_column(column);
+    }
+
+    /// Hides every row of this node's column whose color differs from this node's,
+    /// and marks the rows with the same color as already compatible.
+    fn purify(&mut self, node: usize) {
+        let column = self.nodes[node].column as usize;
+        let color = self.nodes[node].color;
+        self.columns[column].color = color;
+        let header = self.columns[column].header as usize;
+        let mut current = self.nodes[header].down as usize;
+        while current != header {
+            if self.nodes[current].color == color {
+                if current != node {
+                    self.nodes[current].color = PURIFIED;
+                }
+            } else {
+                self.hide(current);
+            }
+            current = self.nodes[current].down as usize;
+        }
+    }
+
+    /// Reverts `purify`, going through the column in reverse order.
+    fn unpurify(&mut self, node: usize) {
+        let column = self.nodes[node].column as usize;
+        let color = self.columns[column].color;
+        let header = self.columns[column].header as usize;
+        let mut current = self.nodes[header].up as usize;
+        while current != header {
+            if self.nodes[current].color == PURIFIED {
+                self.nodes[current].color = color;
+            } else if current != node {
+                self.unhide(current);
+            }
+            current = self.nodes[current].up as usize;
+        }
+    }
+
+    /// Unlinks every other node of this row from its column.
+    ///
+    /// Nodes on a column purified with their own color are skipped: such a column
+    /// is never traversed again until it is unpurified.
+    fn hide(&mut self, node: usize) {
+        let mut updates = 0;
+        let mut other = self.next_in_row(node);
+        while other != node {
+            if self.nodes[other].color != PURIFIED {
+                self.unlink_node(other);
+                updates += 1;
+            }
+            other = self.next_in_row(other);
+        }
+        *self.updates += updates;
+    }
+
+    /// Reverts `hide` by relinking the other nodes of this row in reverse order.
+    fn unhide(&mut self, node: usize) {
+        let mut other = self.previous_in_row(node);
+        while other != node {
+            if self.nodes[other].color != PURIFIED {
+                self.relink_node(other);
+            }
+            other = self.previous_in_row(other);
+        }
+    }
+
+    fn unlink_node(&mut self, node: usize) {
+        let Node {
+            up, down, column, ..
+        } = self.nodes[node];
+        self.nodes[up as usize].down = down;
+        self.nodes[down as usize].up = up;
+        self.columns[column as usize].len -= 1;
+    }
+
+    fn relink_node(&mut self, node: usize) {
+        let Node {
+            up, down, column, ..
+        } = self.nodes[node];
+        self.nodes[up as usize].down = node as u32;
+        self.nodes[down as usize].up = node as u32;
+        self.columns[column as usize].len += 1;
+    }
+
+    fn unlink_column(&mut self, column: usize) {
+        let Column { left, right, .. } = self.columns[column];
+        self.columns[left as usize].right = right;
+        self.columns[right as usize].left = left;
+    }
+
+    fn relink_column(&mut self, column: usize) {
+        let Column { left, right, .. } = self.columns[column];
+        self.columns[left as usize].right = column as u32;
+        self.columns[right as usize].left = column as u32;
+    }
+}
+
+/// Returns the index of the row the node belongs to, as kept by the spacer before it.
+fn row_of(nodes: &[Node], node: usize) -> usize {
+    let mut current = node - 1;
+    while nodes[current].column != SPACER {
+        current -= 1;
+    }
+    nodes[current].color as usize
+}
+
+fn set_label(labels: &mut Vec<Option<String>>, index: usize, label: String) {
+    if index >= labels.len() {
+        labels.resize(index + 1, None);
+    }
+    labels[index] = Some(label);
+}
+
+fn set_flag(flags: &mut Vec<bool>, index: usize) {
+    if index >= flags.len() {
+        flags.resize(index + 1, false);
+    }
+    flags[index] = true;
+}
+
+/// Returns the indices of the flags that are set.
+fn flagged(flags: &[bool]) -> Vec<usize> {
+    (0..flags.len()).filter(|&i| flags[i]).collect()
+}
+
+/// Parses the multiplicity `lo:hi` or `hi` of a primary item in the DLX format.
+fn parse_multiplicity(multiplicity: &str) -> Option<(usize, usize)> {
+    match multiplicity.split_once(':') {
+        Some((lo, hi)) => Some((lo.parse().ok()?, hi.parse().ok()?)),
+        None => {
+            let hi = multiplicity.parse().ok()?;
+            Some((hi, hi))
+        }
+    }
+}
+
+fn invalid_dlx_line(line_number: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid DLX input on line {}: {}", line_number, message),
+    )
+}
+
+fn invalid_dlx_input(message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("matrix can not be written in the DLX format: {}", message),
+    )
+}
+
+fn invalid_checkpoint(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Path from the root of the search tree to one of its nodes: the column branched on
+/// at each level and the node of the row taken from it, or the column's header when
+/// the column is left out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SearchPath(Vec<(u32, u32)>);
+
+/// A primary column not yet covered, as seen by a `ColumnChooser` or a `Pruner`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActiveColumn {
+    /// Index of the column, in the order columns were appended
+    pub index: usize,
+    /// Number of rows that can still cover the column
+    pub len: usize,
+    /// Number of branches the search would explore for the column, see
+    /// `DancingLinks::choose_column`
+    pub branching_degree: usize,
+}
+
+/// Iterator over the primary columns not yet covered, in the order they were appended.
+pub struct ActiveColumns<'a> {
+    columns: &'a [Column],
+    current: usize,
+}
+
+impl Iterator for ActiveColumns<'_> {
+    type Item = ActiveColumn;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current == ROOT {
+            return None;
+        }
+        let column = &self.columns[self.current];
+        let active = ActiveColumn {
+            index: self.current - FIRST_COLUMN,
+            len: column.len as usize,
+            branching_degree: column.branching_degree(),
+        };
+        self.current = column.right as usize;
+        Some(active)
+    }
+}
+
+impl Default for DancingLinks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator over all exact covers of a `DancingLinks` matrix.
+///
+/// Returned by [`DancingLinks::solutions`].
+pub struct Solutions<'a> {
+    dlx: &'a mut DancingLinks,
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.dlx.next_solution()
+    }
+}
+
+impl Drop for Solutions<'_> {
+    fn drop(&mut self) {
+        self.dlx.reset_search();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum SearchState {
+    /// No search in progress, the matrix is fully linked
+    Idle,
+    /// The stack holds the solution returned last
+    AtSolution,
+    /// Paused before entering the node on top of the stack
+    Descending,
+    /// Paused before backtracking from the node on top of the stack
+    Backtracking,
+    /// Every branch has been explored
+    Exhausted,
+}
+
+/// Outcome of [`DancingLinks::step`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchStep {
+    /// An exact cover was found, given as row indices
+    Solution(Vec<usize>),
+    /// The step budget ran out, the next call resumes the search
+    Paused,
+    /// Every branch has been explored
+    Exhausted,
+}
+
+/// One level of the search: the column branched on and the row currently selected from it.
+#[derive(Clone, Copy)]
+struct Level {
+    column: u32,
+    /// The column's header once every row was tried and the column is being left out
+    node: u32,
+    /// Where this level's rows start in `DancingLinks::tweaks`
+    tweaks_start: usize,
+    /// Index of the branch being explored, out of `n_branches`
+    branch: u32,
+    n_branches: u32,
+}
+
+#[derive(Clone, Copy)]
+struct Column {
+    /// Neighbors in the list of columns still to be covered
+    left: u32,
+    right: u32,
+    /// Node closing the column's vertical list
+    header: u32,
+    /// Number of rows currently linked in the column
+    len: u32,
+    secondary: bool,
+    /// How many more times the column may be covered
+    bound: u32,
+    /// How many of the remaining covers are optional
+    slack: u32,
+    /// Color the column is currently purified with
+    color: u32,
+}
+
+impl Column {
+    fn root(slot: usize) -> Self {
+        Self {
+            left: slot as u32,
+            right: slot as u32,
+            // List heads have no vertical list
+            header: u32::MAX,
+            len: 0,
+            secondary: false,
+            bound: 0,
+            slack: 0,
+            color: 0,
+        }
+    }
+
+    /// Whether the column can not be covered any more and did not have to be left out,
+    /// in which case branching on it is the plain exact cover step.
+    fn is_fully_used(&self) -> bool {
+        self.bound == 0 && self.slack == 0
+    }
+
+    fn branching_degree(&self) -> usize {
+        let required = self.bound.saturating_sub(self.slack);
+        (self.len + 1).saturating_sub(required) as usize
+    }
+}
+
+/// A node of the matrix, a column header or a spacer between rows.
+///
+/// A spacer's `up` is the first node of the row before it, its `down` the last node
+/// of the row after it, and its `color` the index of the row after it.
+#[derive(Clone, Copy)]
+struct Node {
+    up: u32,
+    down: u32,
+    /// Slot of the node's column in `DancingLinks::columns`, `SPACER` for spacers
+    column: u32,
+    /// Color on a secondary column, 0 if uncolored
+    color: u32,
+}
+
+/// Matrix of the n-queens problem: ranks and files are primary columns, diagonals
+/// secondary ones, and the queen on rank `r` and file `c` is row `r * n + c`.
+#[cfg(test)]
+pub(crate) fn n_queens(n: usize) -> DancingLinks {
+    let rows: Vec<Vec<usize>> = (0..n)
+        .flat_map(|r| (0..n).map(move |c| (r, c)))
+        .map(|(r, c)| {
+            vec![
+                r,
+                n + c,
+                2 * n + r + c,
+                2 * n + (2 * n - 1) + (n - 1 + r - c),
+            ]
+        })
+        .collect();
+    DancingLinks::from_sparse_rows(2 * n, 2 * (2 * n - 1), &rows).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column_chooser::{FirstColumn, Mrv, MrvWithPriority, RandomizedMrv};
+    use crate::pruner::Pruner;
+    use crate::solve_options::CancellationToken;
+    use crate::trace::TraceEvent;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_dancing_links_simple() {
+        let _ = env_logger::try_init();
+        let mut dlx = DancingLinks::from_vecs(&vec![vec![1, 0], vec![0, 1]], 2).unwrap();
+        assert_eq!(dlx.n_rows, 2);
+        assert_eq!(dlx.n_cols, 2);
+        assert_eq!(dlx.to_vecs(), vec![vec![1, 0], vec![0, 1]]);
+        assert_eq!(dlx.solve(), Some(vec![0, 1]));
+    }
+
+    #[test]
+    fn test_dancing_links_complex() {
+        let _ = env_logger::try_init();
+        let mut dlx = DancingLinks::from_vecs(
+            &vec![
+                vec![1, 0, 0, 1, 0, 0, 1],
+                vec![1, 0, 0, 1, 0, 0, 0],
+                vec![0, 0, 0, 1, 1, 0, 1],
+                vec![0, 0, 1, 0, 1, 1, 0],
+                vec![0, 1, 0, 0, 0, 1, 1],
+                vec![0, 1, 0, 0, 0, 0, 1],
+            ],
+            7,
+        )
+        .unwrap();
+
+        assert_eq!(dlx.n_rows, 6);
+        assert_eq!(dlx.n_cols, 7);
+
+        let solution = dlx.solve();
+        assert!(solution.is_some());
+        let mut sol = solution.unwrap();
+        sol.sort(); // Sort for consistent comparison
+        assert_eq!(sol, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn test_dancing_links_no_solution() {
+        let _ = env_logger::try_init();
+        let mut dlx = DancingLinks::from_vecs(&vec![vec![1, 0], vec![1, 0]], 2).unwrap();
+
+        assert_eq!(dlx.solve(), None);
+    }
+
+    #[test]
+    fn test_dancing_links_single_row() {
+        // Matrix with single row covering all columns:
         // [1, 1, 1]  <- row 0
         let _ = env_logger::try_init();
-        let mut dlx = DancingLinks::from_vecs(&vec![vec![1, 1, 1]], 3);
+        let mut dlx = DancingLinks::from_vecs(&vec![vec![1, 1, 1]], 3).unwrap();
+
+        assert_eq!(dlx.solve(), Some(vec![0]));
+    }
+
+    #[test]
+    fn test_dancing_links_all_solutions() {
+        let _ = env_logger::try_init();
+        let mut dlx = DancingLinks::from_vecs(
+            &vec![
+                vec![1, 0, 0],
+                vec![0, 1, 1],
+                vec![1, 1, 0],
+                vec![0, 0, 1],
+                vec![0, 1, 0],
+            ],
+            3,
+        )
+        .unwrap();
+
+        let mut solutions: Vec<Vec<usize>> = dlx
+            .solutions()
+            .map(|mut sol| {
+                sol.sort();
+                sol
+            })
+            .collect();
+        solutions.sort();
+        assert_eq!(solutions, vec![vec![0, 1], vec![0, 3, 4], vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_dancing_links_solutions_stop_early() {
+        let _ = env_logger::try_init();
+        let matrix = vec![
+            vec![1, 0, 0],
+            vec![0, 1, 1],
+            vec![1, 1, 0],
+            vec![0, 0, 1],
+            vec![0, 1, 0],
+        ];
+        let mut dlx = DancingLinks::from_vecs(&matrix, 3).unwrap();
+
+        assert_eq!(dlx.solutions().take(2).count(), 2);
+        // Dropping the iterator mid-search restores the matrix
+        assert_eq!(dlx.to_vecs(), matrix);
+        assert_eq!(dlx.solutions().count(), 3);
+    }
+
+    #[test]
+    fn test_dancing_links_count_solutions() {
+        let _ = env_logger::try_init();
+        let matrix = vec![
+            vec![1, 0, 0],
+            vec![0, 1, 1],
+            vec![1, 1, 0],
+            vec![0, 0, 1],
+            vec![0, 1, 0],
+        ];
+        let mut dlx = DancingLinks::from_vecs(&matrix, 3).unwrap();
+        assert_eq!(dlx.count_solutions(), 3);
+        assert_eq!(dlx.to_vecs(), matrix);
+
+        let mut dlx = DancingLinks::from_vecs(&vec![vec![1, 0], vec![1, 0]], 2).unwrap();
+        assert_eq!(dlx.count_solutions(), 0);
+    }
+
+    #[test]
+    fn test_dancing_links_step() {
+        let _ = env_logger::try_init();
+        let matrix = vec![
+            vec![1, 0, 0],
+            vec![0, 1, 1],
+            vec![1, 1, 0],
+            vec![0, 0, 1],
+            vec![0, 1, 0],
+        ];
+        let mut dlx = DancingLinks::from_vecs(&matrix, 3).unwrap();
+        let expected: Vec<Vec<usize>> = dlx.solutions().collect();
+
+        // A single step at a time visits the same solutions in the same order
+        let mut found = Vec::new();
+        let mut n_paused = 0;
+        loop {
+            match dlx.step(1) {
+                SearchStep::Solution(solution) => found.push(solution),
+                SearchStep::Paused => n_paused += 1,
+                SearchStep::Exhausted => break,
+            }
+        }
+        assert_eq!(found, expected);
+        assert!(n_paused > 0);
+        assert_eq!(dlx.step(1), SearchStep::Exhausted);
+
+        dlx.reset_search();
+        assert_eq!(dlx.to_vecs(), matrix);
+        assert_eq!(
+            dlx.step(u64::MAX),
+            SearchStep::Solution(expected[0].clone())
+        );
+    }
+
+    #[test]
+    fn test_dancing_links_solve_with_options() {
+        let _ = env_logger::try_init();
+        let matrix = vec![
+            vec![1, 0, 0],
+            vec![0, 1, 1],
+            vec![1, 1, 0],
+            vec![0, 0, 1],
+            vec![0, 1, 0],
+        ];
+        let mut dlx = DancingLinks::from_vecs(&matrix, 3).unwrap();
+        let unlimited = SolveOptions::default();
+        assert_eq!(
+            dlx.solve_with_options(&unlimited),
+            SolveOutcome::Solved(dlx.solve().unwrap())
+        );
+
+        let max_nodes = SolveOptions {
+            max_nodes: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            dlx.solve_with_options(&max_nodes),
+            SolveOutcome::LimitReached {
+                limit: Limit::Nodes,
+                nodes: 2
+            }
+        );
+        assert_eq!(dlx.to_vecs(), matrix);
+
+        let token = CancellationToken::new();
+        let cancelled = SolveOptions {
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        token.cancel();
+        assert_eq!(
+            dlx.solve_with_options(&cancelled),
+            SolveOutcome::LimitReached {
+                limit: Limit::Cancelled,
+                nodes: 0
+            }
+        );
+
+        let expired = SolveOptions::default().with_time_limit(Duration::ZERO);
+        assert_eq!(
+            dlx.solve_with_options(&expired),
+            SolveOutcome::LimitReached {
+                limit: Limit::Deadline,
+                nodes: 0
+            }
+        );
+
+        let mut dlx = DancingLinks::from_vecs(&vec![vec![1, 0], vec![1, 0]], 2).unwrap();
+        assert_eq!(dlx.solve_with_options(&unlimited), SolveOutcome::Unsolvable);
+    }
+
+    #[test]
+    fn test_dancing_links_clone_on_another_thread() {
+        let _ = env_logger::try_init();
+        let matrix = vec![
+            vec![1, 0, 0],
+            vec![0, 1, 1],
+            vec![1, 1, 0],
+            vec![0, 0, 1],
+            vec![0, 1, 0],
+        ];
+        let mut dlx = DancingLinks::from_vecs(&matrix, 3).unwrap();
+        let mut copy = dlx.clone();
+        let handle = std::thread::spawn(move || copy.count_solutions());
+        assert_eq!(dlx.count_solutions(), 3);
+        assert_eq!(handle.join().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_dancing_links_secondary_columns() {
+        let _ = env_logger::try_init();
+        // Column 1 is secondary, so row 1 may leave it uncovered
+        let mut dlx =
+            DancingLinks::from_vecs_with_secondary(&[vec![1, 1], vec![1, 0]], 1, 1).unwrap();
+        assert_eq!(dlx.n_cols, 2);
+        assert_eq!(dlx.to_vecs(), vec![vec![1, 1], vec![1, 0]]);
+        assert_eq!(dlx.count_solutions(), 2);
+
+        // ... but still at most once
+        let mut dlx = DancingLinks::new();
+        dlx.append_column();
+        dlx.append_column();
+        dlx.append_secondary_column();
+        dlx.append_row(&[1, 0, 1]).unwrap();
+        dlx.append_row(&[0, 1, 1]).unwrap();
+        dlx.append_row(&[0, 1, 0]).unwrap();
+        assert_eq!(dlx.solve(), Some(vec![0, 2]));
+        assert_eq!(dlx.count_solutions(), 1);
+    }
+
+    #[test]
+    fn test_dancing_links_sparse_rows() {
+        let _ = env_logger::try_init();
+        let matrix = vec![
+            vec![1, 0, 0, 1],
+            vec![0, 1, 1, 0],
+            vec![1, 1, 0, 0],
+            vec![0, 0, 1, 0],
+            vec![0, 0, 0, 0],
+            vec![0, 1, 0, 1],
+        ];
+        let sparse: Vec<Vec<usize>> = matrix
+            .iter()
+            .map(|row| (0..row.len()).filter(|&i| row[i] != 0).collect())
+            .collect();
+        let mut dense = DancingLinks::from_vecs_with_secondary(&matrix, 3, 1).unwrap();
+        let mut dlx = DancingLinks::from_sparse_rows(3, 1, &sparse).unwrap();
+        assert_eq!(dlx.to_vecs(), matrix);
+        assert_eq!(dlx.nodes.len(), dense.nodes.len());
+        assert_eq!(
+            dlx.solutions().collect::<Vec<_>>(),
+            dense.solutions().collect::<Vec<_>>()
+        );
+
+        // Rows can be added one at a time and mixed with dense ones
+        let mut dlx = DancingLinks::new();
+        dlx.append_column();
+        dlx.append_column();
+        dlx.append_sparse_row(&[0, 1]).unwrap();
+        dlx.append_row(&[1, 0]).unwrap();
+        dlx.append_sparse_row(&[1]).unwrap();
+        dlx.append_sparse_row(&[]).unwrap();
+        assert_eq!(
+            dlx.to_vecs(),
+            vec![vec![1, 1], vec![1, 0], vec![0, 1], vec![0, 0]]
+        );
+        assert_eq!(dlx.count_solutions(), 2);
+    }
+
+    #[test]
+    fn test_dancing_links_labels() {
+        let _ = env_logger::try_init();
+        let mut dlx = DancingLinks::from_sparse_rows(2, 0, [vec![0], vec![1], vec![0, 1]]).unwrap();
+        dlx.set_column_label(1, "b").unwrap();
+        dlx.set_row_label(0, "first").unwrap();
+        dlx.set_row_label(2, "both").unwrap();
+        assert_eq!(dlx.column_label(0), None);
+        assert_eq!(dlx.column_label(1), Some("b"));
+        assert_eq!(dlx.row_label(1), None);
+        assert_eq!(dlx.row_label(2), Some("both"));
+        assert_eq!(
+            dlx.set_row_label(3, "none"),
+            Err(DlxError::RowOutOfRange { row: 3, n_rows: 3 })
+        );
+        assert_eq!(
+            dlx.set_column_label(2, "none"),
+            Err(DlxError::ColumnOutOfRange {
+                column: 2,
+                n_cols: 2
+            })
+        );
+
+        let solutions: Vec<Vec<String>> = dlx
+            .clone()
+            .solutions()
+            .map(|solution| dlx.solution_labels(&solution))
+            .collect();
+        assert_eq!(solutions, vec![vec!["first", "1"], vec!["both"]]);
+
+        // Labels are kept by checkpoints
+        let restored = DancingLinks::from_checkpoint(&dlx.checkpoint()).unwrap();
+        assert_eq!(restored.row_label(0), Some("first"));
+        assert_eq!(restored.row_label(1), None);
+        assert_eq!(restored.column_label(1), Some("b"));
+    }
+
+    #[test]
+    fn test_dancing_links_read_dlx() {
+        let _ = env_logger::try_init();
+        let text = "\
+| A simple example of color controls
+A B C | X Y
+A B X:0 Y:0
+A C X:1 Y:1
+  X:0 Y:1
+
+B X:1
+C Y:1
+";
+        let mut dlx = DancingLinks::read_dlx(text.as_bytes()).unwrap();
+        assert_eq!(dlx.column_label(0), Some("A"));
+        assert_eq!(dlx.column_label(4), Some("Y"));
+        let solutions: Vec<Vec<usize>> = dlx.solutions().collect();
+        assert_eq!(solutions, vec![vec![1, 3]]);
+
+        let text = "2:3|A 2|B |C\nA B C:x\nA B\nB\nA\n";
+        let mut dlx = DancingLinks::read_dlx(text.as_bytes()).unwrap();
+        // B twice out of its 3 options, then A once more if needed or wanted
+        assert_eq!(dlx.count_solutions(), 4);
+
+        let invalid = [
+            ("A B\nA C\n", 2, "unknown item C"),
+            ("A A\n", 1, "duplicate item name A"),
+            ("A | B\nA:1\n", 2, "primary"),
+            ("A | B\nA B A\n", 2, "appears twice"),
+            ("A | 2|B\n", 1, "multiplicity"),
+            ("0|A\n", 1, "multiplicity"),
+            ("x:1|A\n", 1, "invalid multiplicity x:1"),
+        ];
+        for (text, line, message) in invalid {
+            let error = DancingLinks::read_dlx(text.as_bytes()).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            let error = error.to_string();
+            assert!(error.contains(&format!("line {}", line)), "{}", error);
+            assert!(error.contains(message), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_dancing_links_write_dlx() {
+        let _ = env_logger::try_init();
+        let mut dlx = DancingLinks::new();
+        dlx.append_column_with_multiplicity(1, 2).unwrap();
+        dlx.append_secondary_column();
+        dlx.append_column();
+        dlx.append_column_with_multiplicity(2, 2).unwrap();
+        dlx.append_colored_row(&[1, 1, 0, 1], &[0, 3, 0, 0])
+            .unwrap();
+        dlx.append_row(&[0, 0, 1, 1]).unwrap();
+        dlx.append_colored_row(&[1, 1, 0, 1], &[0, 3, 0, 0])
+            .unwrap();
+        dlx.append_row(&[1, 0, 1, 0]).unwrap();
+        dlx.set_column_label(2, "c").unwrap();
+        dlx.set_row_label(1, "second").unwrap();
+        // A search in progress does not change what is written
+        assert_eq!(dlx.step(2), SearchStep::Paused);
+
+        let mut text = Vec::new();
+        dlx.write_dlx(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text.clone()).unwrap(),
+            "\
+| 3 primary and 1 secondary items, 4 options
+1:2|0 c 2|3 | 1
+0 1:3 3
+| second
+c 3
+0 1:3 3
+0 c
+"
+        );
+
+        let mut read = DancingLinks::read_dlx(text.as_slice()).unwrap();
+        dlx.reset_search();
+        let mut expected: Vec<Vec<usize>> = dlx.solutions().collect();
+        let mut actual: Vec<Vec<usize>> = read.solutions().collect();
+        expected.iter_mut().for_each(|solution| solution.sort());
+        actual.iter_mut().for_each(|solution| solution.sort());
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+        assert_eq!(read.column_label(3), Some("1"));
+
+        dlx.set_column_label(0, "c").unwrap();
+        let error = dlx.write_dlx(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        dlx.set_column_label(0, "a b").unwrap();
+        assert!(dlx.write_dlx(&mut Vec::new()).is_err());
+        dlx.set_column_label(0, "a").unwrap();
+        dlx.append_sparse_row(&[]).unwrap();
+        assert!(dlx.write_dlx(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_dancing_links_invalid_rows() {
+        let _ = env_logger::try_init();
+        assert_eq!(
+            DancingLinks::from_vecs(&vec![vec![1, 0], vec![1]], 2).err(),
+            Some(DlxError::RowLength {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            DancingLinks::from_sparse_rows(2, 1, [vec![1, 3]]).err(),
+            Some(DlxError::ColumnOutOfRange {
+                column: 3,
+                n_cols: 3
+            })
+        );
+
+        let mut dlx = DancingLinks::from_sparse_rows(2, 1, [[0, 2]]).unwrap();
+        assert_eq!(
+            dlx.append_sparse_row(&[2, 1]),
+            Err(DlxError::ColumnsNotIncreasing)
+        );
+        assert_eq!(
+            dlx.append_sparse_row(&[1, 1]),
+            Err(DlxError::ColumnsNotIncreasing)
+        );
+        assert_eq!(
+            dlx.append_column_with_multiplicity(2, 1),
+            Err(DlxError::InvalidMultiplicity { lo: 2, hi: 1 })
+        );
+        assert_eq!(
+            dlx.append_colored_row(&[0, 1, 1], &[0, 1]),
+            Err(DlxError::ColorsLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            dlx.append_colored_row(&[0, 1, 0], &[0, 0, 1]),
+            Err(DlxError::ColorOnUncoveredColumn { column: 2 })
+        );
+        assert_eq!(
+            dlx.append_colored_row(&[0, 1, 1], &[0, 1, 0]),
+            Err(DlxError::ColorOnPrimaryColumn { column: 1 })
+        );
+
+        // Rejected rows leave the matrix untouched
+        assert_eq!(dlx.to_vecs(), vec![vec![1, 0, 1]]);
+        assert_eq!(dlx.count_solutions(), 0);
+        dlx.append_sparse_row(&[1]).unwrap();
+        assert_eq!(dlx.count_solutions(), 1);
+    }
+
+    #[test]
+    fn test_dancing_links_n_queens() {
+        let _ = env_logger::try_init();
+        let mut dlx = n_queens(8);
+        assert_eq!(dlx.count_solutions(), 92);
+    }
+
+    #[test]
+    fn test_dancing_links_zdd() {
+        let _ = env_logger::try_init();
+        let n = 8;
+        let mut dlx = n_queens(n);
+        let mut expected: Vec<Vec<usize>> = dlx
+            .solutions()
+            .map(|mut solution| {
+                solution.sort();
+                solution
+            })
+            .collect();
+        expected.sort();
+
+        let zdd = dlx.zdd().unwrap();
+        assert_eq!(zdd.count(), 92);
+        assert_eq!(dlx.stats().solutions, 92);
+        assert_eq!(dlx.count_solutions_memoized().unwrap(), 92);
+        let mut actual = Vec::new();
+        zdd.for_each_solution(|solution| {
+            let mut solution = solution.to_vec();
+            solution.sort();
+            actual.push(solution);
+        });
+        actual.sort();
+        assert_eq!(actual, expected);
+        assert!(expected.iter().all(|solution| zdd.contains(solution)));
+        assert!(!zdd.contains(&expected[0][1..]));
+
+        let corner = 0;
+        let containing = expected.iter().filter(|s| s.contains(&corner)).count();
+        assert_eq!(zdd.count_containing(corner), containing as u128);
+        assert!(
+            zdd.sample(50, 1)
+                .iter()
+                .all(|solution| zdd.contains(solution))
+        );
+
+        // The search is left as built
+        assert_eq!(dlx.count_solutions(), 92);
+    }
+
+    #[test]
+    fn test_dancing_links_zdd_matches_enumeration() {
+        let _ = env_logger::try_init();
+        let mut rng = SplitMix64::new(0x9e37_79b9_7f4a_7c15);
+
+        for _ in 0..200 {
+            let n_primary = 1 + rng.below(6);
+            let n_secondary = rng.below(3);
+            let n_cols = n_primary + n_secondary;
+            let n_rows = 1 + rng.below(14);
+            let matrix: Vec<Vec<usize>> = (0..n_rows)
+                .map(|_| (0..n_cols).map(|_| (rng.below(3) == 0) as usize).collect())
+                .collect();
+            let mut dlx =
+                DancingLinks::from_vecs_with_secondary(&matrix, n_primary, n_secondary).unwrap();
+            let mut expected: Vec<Vec<usize>> = dlx
+                .solutions()
+                .map(|mut solution| {
+                    solution.sort();
+                    solution
+                })
+                .collect();
+            expected.sort();
+
+            let zdd = dlx.zdd().unwrap();
+            let mut actual = Vec::new();
+            zdd.for_each_solution(|solution| {
+                let mut solution = solution.to_vec();
+                solution.sort();
+                actual.push(solution);
+            });
+            actual.sort();
+            assert_eq!(actual, expected, "matrix={:?}", matrix);
+            assert_eq!(zdd.count(), expected.len() as u128);
+            assert_eq!(dlx.to_vecs(), matrix);
+        }
+    }
+
+    #[test]
+    fn test_dancing_links_zdd_shares_subproblems() {
+        let _ = env_logger::try_init();
+        // Pairs of columns each covered by two interchangeable rows: 2^n solutions
+        let n = 40;
+        let rows: Vec<Vec<usize>> = (0..n)
+            .flat_map(|pair| [vec![2 * pair, 2 * pair + 1], vec![2 * pair, 2 * pair + 1]])
+            .collect();
+        let mut dlx = DancingLinks::from_sparse_rows(2 * n, 0, &rows).unwrap();
+        let zdd = dlx.zdd().unwrap();
+        assert_eq!(zdd.count(), 1 << n);
+        assert!(zdd.nodes().len() <= 2 + 2 * n);
+        assert!(dlx.stats().nodes <= 1 + 2 * n as u64);
+        assert_eq!(zdd.count_containing(0), 1 << (n - 1));
+    }
+
+    #[test]
+    fn test_dancing_links_zdd_unsupported() {
+        let mut dlx = DancingLinks::new();
+        dlx.append_column_with_multiplicity(1, 2).unwrap();
+        dlx.append_row(&[1]).unwrap();
+        assert_eq!(dlx.zdd(), Err(DlxError::MemoizationUnsupported));
+
+        let mut dlx = DancingLinks::new();
+        dlx.append_column();
+        dlx.append_secondary_column();
+        dlx.append_colored_row(&[1, 1], &[0, 2]).unwrap();
+        assert_eq!(
+            dlx.count_solutions_memoized(),
+            Err(DlxError::MemoizationUnsupported)
+        );
+        assert_eq!(dlx.count_solutions(), 1);
+    }
+
+    #[test]
+    fn test_dancing_links_force_rows() {
+        let _ = env_logger::try_init();
+        let n = 8;
+        let mut dlx = n_queens(n);
+        let all: Vec<Vec<usize>> = dlx.solutions().collect();
+        // Queens on (0, 0) and (1, 4), given as rows r * n + c
+        let forced = [0, n + 4];
+        let mut expected: Vec<Vec<usize>> = all
+            .iter()
+            .filter(|solution| forced.iter().all(|row| solution.contains(row)))
+            .cloned()
+            .collect();
+        assert!(!expected.is_empty());
+        let mut actual: Vec<Vec<usize>> = dlx.solutions_with_prefix(&forced).unwrap().collect();
+        assert!(actual.iter().all(|solution| solution[..2] == forced));
+        for solutions in [&mut expected, &mut actual] {
+            solutions.iter_mut().for_each(|solution| solution.sort());
+            solutions.sort();
+        }
+        assert_eq!(actual, expected);
+        assert_eq!(
+            dlx.solve_with_prefix(&forced).unwrap().map(|s| s.len()),
+            Some(n)
+        );
+
+        // The forced rows stay until the search is reset
+        dlx.force_rows(&[0]).unwrap();
+        let mut found = 0;
+        loop {
+            match dlx.step(100) {
+                SearchStep::Solution(solution) => {
+                    assert_eq!(solution[0], 0);
+                    found += 1;
+                }
+                SearchStep::Paused => {}
+                SearchStep::Exhausted => break,
+            }
+        }
+        let corner = all.iter().filter(|solution| solution.contains(&0));
+        assert_eq!(found, corner.count());
+        let exhausted = dlx.checkpoint();
+        assert!(exhausted.is_exhausted());
+        let mut restored = DancingLinks::from_checkpoint(&exhausted).unwrap();
+        assert_eq!(restored.step(100), SearchStep::Exhausted);
+
+        // A checkpoint keeps the rows forced
+        dlx.force_rows(&forced).unwrap();
+        let first = loop {
+            if let SearchStep::Solution(solution) = dlx.step(1) {
+                break solution;
+            }
+        };
+        let mut restored = DancingLinks::from_checkpoint(&dlx.checkpoint()).unwrap();
+        let mut rest = Vec::new();
+        while let Some(solution) = restored.next_solution() {
+            rest.push(solution);
+        }
+        assert_eq!(1 + rest.len(), expected.len());
+        assert!(!rest.contains(&first));
+        dlx.reset_search();
+        assert_eq!(dlx.count_solutions(), all.len() as u128);
+
+        // Same rank, then same diagonal
+        assert_eq!(
+            dlx.solve_with_prefix(&[0, 3]),
+            Err(DlxError::ForcedRowConflict { row: 3 })
+        );
+        assert_eq!(
+            dlx.solve_with_prefix(&[n + 1, 0]),
+            Err(DlxError::ForcedRowConflict { row: 0 })
+        );
+        assert_eq!(
+            dlx.solve_with_prefix(&[0, 0]),
+            Err(DlxError::ForcedRowConflict { row: 0 })
+        );
+        assert_eq!(
+            dlx.force_rows(&[n * n]),
+            Err(DlxError::RowOutOfRange {
+                row: n * n,
+                n_rows: n * n
+            })
+        );
+        assert_eq!(dlx.count_solutions(), all.len() as u128);
+
+        // Two rows can be forced in a column covered up to twice, but not the same one
+        let mut dlx = DancingLinks::new();
+        dlx.append_column_with_multiplicity(1, 2).unwrap();
+        dlx.append_secondary_column();
+        dlx.append_row(&[1, 0]).unwrap();
+        dlx.append_row(&[1, 0]).unwrap();
+        dlx.append_row(&[0, 1]).unwrap();
+        assert_eq!(dlx.solve_with_prefix(&[1, 0]).unwrap(), Some(vec![1, 0]));
+        assert_eq!(dlx.solutions_with_prefix(&[1]).unwrap().count(), 2);
+        assert_eq!(
+            dlx.force_rows(&[1, 1]),
+            Err(DlxError::ForcedRowConflict { row: 1 })
+        );
+        assert_eq!(
+            dlx.force_rows(&[2]),
+            Err(DlxError::RowWithoutPrimaryColumn { row: 2 })
+        );
+        dlx.append_sparse_row(&[]).unwrap();
+        assert_eq!(
+            dlx.force_rows(&[3]),
+            Err(DlxError::RowWithoutPrimaryColumn { row: 3 })
+        );
+    }
+
+    #[test]
+    fn test_dancing_links_remove_rows_and_columns() {
+        let _ = env_logger::try_init();
+        let rows = vec![vec![0, 1], vec![2], vec![0], vec![1, 2], vec![0, 1, 2]];
+        let mut dlx = DancingLinks::from_sparse_rows(3, 0, &rows).unwrap();
+        assert_eq!(dlx.count_solutions(), 3);
+
+        // Removing a row resets the search and keeps the other rows' indices
+        assert!(dlx.next_solution().is_some());
+        dlx.remove_row(4).unwrap();
+        assert!(dlx.is_row_removed(4));
+        assert_eq!(dlx.to_vecs()[4], vec![0, 0, 0]);
+        let mut solutions: Vec<Vec<usize>> = dlx.solutions().collect();
+        solutions.iter_mut().for_each(|solution| solution.sort());
+        solutions.sort();
+        assert_eq!(solutions, vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(dlx.remove_row(4), Err(DlxError::RowRemoved { row: 4 }));
+        assert_eq!(
+            dlx.remove_row(5),
+            Err(DlxError::RowOutOfRange { row: 5, n_rows: 5 })
+        );
+        assert_eq!(dlx.force_rows(&[4]), Err(DlxError::RowRemoved { row: 4 }));
 
+        // A column can only go once it has no rows
+        assert_eq!(
+            dlx.remove_column(2),
+            Err(DlxError::ColumnNotEmpty { column: 2 })
+        );
+        dlx.remove_row(1).unwrap();
+        dlx.remove_row(3).unwrap();
+        assert_eq!(dlx.count_solutions(), 0);
+        dlx.remove_column(2).unwrap();
+        assert!(dlx.is_column_removed(2));
         assert_eq!(dlx.solve(), Some(vec![0]));
+        assert_eq!(
+            dlx.remove_column(2),
+            Err(DlxError::ColumnRemoved { column: 2 })
+        );
+        assert_eq!(
+            dlx.append_sparse_row(&[1, 2]),
+            Err(DlxError::ColumnRemoved { column: 2 })
+        );
+
+        // Appended rows and columns take the next indices
+        dlx.append_sparse_row(&[1]).unwrap();
+        dlx.append_secondary_column();
+        dlx.append_sparse_row(&[0, 3]).unwrap();
+        let mut solutions: Vec<Vec<usize>> = dlx.solutions().collect();
+        solutions.iter_mut().for_each(|solution| solution.sort());
+        solutions.sort();
+        assert_eq!(solutions, vec![vec![0], vec![2, 5], vec![5, 6]]);
+
+        // Checkpoints and exports leave the removed rows and columns out
+        let checkpoint = dlx.checkpoint();
+        let mut restored = DancingLinks::from_checkpoint(&checkpoint).unwrap();
+        assert!(restored.is_row_removed(3) && restored.is_column_removed(2));
+        assert_eq!(restored.count_solutions(), 3);
+        let mut other = DancingLinks::from_checkpoint(&checkpoint).unwrap();
+        other.remove_row(0).unwrap();
+        assert!(other.restore(&checkpoint).is_err());
+
+        let mut text = Vec::new();
+        dlx.write_dlx(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text.clone()).unwrap(),
+            "| 2 primary and 1 secondary items, 4 options\n0 1 | 3\n0 1\n0\n1\n0 3\n"
+        );
+        let mut read = DancingLinks::read_dlx(text.as_slice()).unwrap();
+        assert_eq!(read.count_solutions(), 3);
+
+        let cnf = dlx.to_cnf(AtMostOne::Pairwise).unwrap();
+        assert!(cnf.clauses().contains(&vec![-Cnf::row_var(4)]));
+    }
+
+    #[test]
+    fn test_dancing_links_progress_callback() {
+        let _ = env_logger::try_init();
+        let n = 8;
+        let mut dlx = n_queens(n);
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        dlx.set_progress_callback(100, move |progress| {
+            sink.lock().unwrap().push(progress.clone())
+        });
+        assert_eq!(dlx.count_solutions(), 92);
+
+        let n_reports = reports.lock().unwrap().len();
+        dlx.clear_progress_callback();
+        assert_eq!(dlx.count_solutions(), 92);
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), n_reports);
+        assert!(reports.len() > 10);
+        for pair in reports.windows(2) {
+            assert!(pair[1].updates >= pair[0].updates + 100);
+            assert!(pair[1].nodes >= pair[0].nodes);
+            assert!(pair[1].solutions >= pair[0].solutions);
+        }
+        for progress in reports.iter() {
+            assert_eq!(progress.depth, progress.branches.len());
+            assert!(progress.solutions <= 92);
+            assert!(
+                progress
+                    .branches
+                    .iter()
+                    .all(|branch| branch.index < branch.count)
+            );
+            let completion = progress.estimated_completion();
+            assert!((0.0..=1.0).contains(&completion));
+        }
+        assert_eq!(reports[0].branches[0].to_string(), "row 1 of 8");
+        drop(reports);
+
+        // An interval of 0 is taken as 1
+        let n_calls = Arc::new(Mutex::new(0));
+        let sink = n_calls.clone();
+        dlx.set_progress_callback(0, move |_| *sink.lock().unwrap() += 1);
+        assert_eq!(dlx.count_solutions(), 92);
+        assert!(*n_calls.lock().unwrap() > n_reports);
+    }
+
+    #[test]
+    fn test_dancing_links_stats() {
+        let _ = env_logger::try_init();
+        let n = 6;
+        let mut dlx = n_queens(n);
+        assert_eq!(dlx.count_solutions(), 4);
+
+        let stats = dlx.stats().clone();
+        assert_eq!(stats.solutions, 4);
+        assert_eq!(stats.nodes, stats.nodes_per_depth.iter().sum::<u64>());
+        assert_eq!(stats.nodes_per_depth[0], 1);
+        // Solutions are found with one queen per rank
+        assert_eq!(stats.max_depth(), n);
+        assert_eq!(stats.nodes_per_depth[n], 4);
+        assert!(stats.updates > stats.nodes);
+        assert!(stats.backtracks > 0);
+
+        // Stepping through the same search gives the same counts
+        dlx.reset_search();
+        while dlx.step(3) != SearchStep::Exhausted {}
+        let stepped = dlx.stats();
+        assert_eq!(stepped.nodes_per_depth, stats.nodes_per_depth);
+        assert_eq!(stepped.updates, stats.updates);
+        assert_eq!(stepped.backtracks, stats.backtracks);
+        assert_eq!(stepped.solutions, stats.solutions);
+
+        // A new search starts from zero
+        dlx.solve();
+        assert_eq!(dlx.stats().solutions, 1);
+        assert!(dlx.stats().nodes < stats.nodes);
+    }
+
+    #[test]
+    fn test_dancing_links_trace() {
+        let _ = env_logger::try_init();
+        let mut dlx =
+            DancingLinks::from_sparse_rows(2, 0, &[vec![0], vec![0, 1], vec![1]]).unwrap();
+        dlx.set_column_label(0, "a").unwrap();
+        dlx.set_row_label(1, "a+1").unwrap();
+        assert!(dlx.take_trace().is_none());
+        dlx.enable_trace(10);
+        assert_eq!(dlx.count_solutions(), 2);
+        let trace = dlx.take_trace().unwrap();
+        let mut dot = Vec::new();
+        trace.write_dot(&mut dot).unwrap();
+        assert_eq!(
+            String::from_utf8(dot).unwrap(),
+            "digraph search {
+  node [shape=box, fontname=\"monospace\"];
+  n0 [label=\"a (2)\"];
+  n0 -> n1 [label=\"0\"];
+  n1 [label=\"1 (1)\"];
+  n1 -> n2 [label=\"2\"];
+  n2 [label=\"solution\", style=filled, fillcolor=palegreen];
+  n0 -> n3 [label=\"a+1\"];
+  n3 [label=\"solution\", style=filled, fillcolor=palegreen];
+}
+"
+        );
+
+        // Every node of the search is recorded, or counted below the depth limit
+        let n = 6;
+        let mut dlx = n_queens(n);
+        for max_depth in [n, 2] {
+            dlx.reset_search();
+            dlx.enable_trace(max_depth);
+            while dlx.step(3) != SearchStep::Exhausted {}
+            let trace = dlx.take_trace().unwrap();
+            let stats = dlx.stats();
+            let events = trace.events();
+            let count = |f: fn(&TraceEvent) -> bool| events.iter().filter(|e| f(e)).count() as u64;
+            let recorded: u64 = stats.nodes_per_depth[..=max_depth].iter().sum();
+            assert_eq!(count(|e| matches!(e, TraceEvent::Enter { .. })), recorded);
+            assert_eq!(count(|e| matches!(e, TraceEvent::Leave { .. })), recorded);
+            assert_eq!(
+                count(|e| !matches!(e, TraceEvent::Enter { .. } | TraceEvent::Leave { .. })),
+                recorded
+            );
+            let solutions = count(|e| matches!(e, TraceEvent::Solution { .. }));
+            assert_eq!(solutions, if max_depth == n { 4 } else { 0 });
+            assert_eq!(
+                events.last(),
+                Some(&TraceEvent::Leave {
+                    id: 0,
+                    hidden: stats.nodes - recorded
+                })
+            );
+        }
+
+        // A new search starts a new trace
+        dlx.enable_trace(0);
+        dlx.solve();
+        dlx.solve();
+        let trace = dlx.take_trace().unwrap();
+        assert_eq!(
+            trace.events()[0],
+            TraceEvent::Enter {
+                id: 0,
+                parent: None,
+                depth: 0,
+                row: None
+            }
+        );
+        assert_eq!(trace.events().len(), 2);
+        dlx.solve();
+        assert!(dlx.take_trace().is_none());
+    }
+
+    /// Gives up on every branch where a row of `banned` is selected.
+    #[derive(Clone)]
+    struct BanRows {
+        banned: Vec<usize>,
+        calls: Arc<Mutex<usize>>,
+    }
+
+    impl Pruner for BanRows {
+        fn keep(&mut self, row: usize, columns: ActiveColumns<'_>) -> bool {
+            *self.calls.lock().unwrap() += 1;
+            assert!(columns.count() > 0);
+            !self.banned.contains(&row)
+        }
+    }
+
+    #[test]
+    fn test_dancing_links_pruner() {
+        let _ = env_logger::try_init();
+        let n = 6;
+        let mut dlx = n_queens(n);
+        let all: Vec<Vec<usize>> = dlx.solutions().collect();
+        let nodes = dlx.stats().nodes;
+
+        // A pruner keeping every branch changes nothing
+        let calls = Arc::new(Mutex::new(0));
+        dlx.set_pruner(Box::new(BanRows {
+            banned: Vec::new(),
+            calls: calls.clone(),
+        }));
+        assert_eq!(dlx.solutions().collect::<Vec<_>>(), all);
+        assert_eq!((dlx.stats().nodes, dlx.stats().pruned), (nodes, 0));
+        assert!(*calls.lock().unwrap() > 0);
+
+        // Banning a row loses exactly the solutions holding it
+        let banned = all[0][1];
+        dlx.set_pruner(Box::new(BanRows {
+            banned: vec![banned],
+            calls,
+        }));
+        let expected: Vec<&Vec<usize>> = all.iter().filter(|s| !s.contains(&banned)).collect();
+        assert_eq!(dlx.count_solutions(), expected.len() as u128);
+        assert!(dlx.stats().pruned > 0);
+        assert!(dlx.stats().nodes < nodes);
+        dlx.enable_trace(n);
+        assert_eq!(dlx.solve().as_ref(), expected.first().copied());
+        let trace = dlx.take_trace().unwrap();
+        assert!(
+            trace
+                .events()
+                .iter()
+                .any(|e| matches!(e, TraceEvent::Pruned { .. }))
+        );
+        // Forced rows are not checked
+        let forced = dlx.solve_with_prefix(&[banned]).unwrap().unwrap();
+        assert_eq!(forced[0], banned);
+    }
+
+    #[test]
+    fn test_dancing_links_column_choosers() {
+        let _ = env_logger::try_init();
+        let n = 6;
+        let mut dlx = n_queens(n);
+        let expected: Vec<Vec<usize>> = dlx.solutions().collect();
+        let default_stats = dlx.stats().clone();
+        let mut sorted_expected = expected.clone();
+        sorted_expected
+            .iter_mut()
+            .for_each(|solution| solution.sort());
+        sorted_expected.sort();
+
+        // The explicit default behaves exactly like no chooser
+        dlx.set_column_chooser(Box::new(Mrv));
+        assert_eq!(dlx.solutions().collect::<Vec<_>>(), expected);
+        assert_eq!(dlx.stats().nodes, default_stats.nodes);
+
+        let choosers: Vec<Box<dyn ColumnChooser>> = vec![
+            Box::new(FirstColumn),
+            Box::new(MrvWithPriority::new((0..2 * n as i32).collect())),
+            Box::new(RandomizedMrv::new(1)),
+            Box::new(RandomizedMrv::new(2)),
+        ];
+        for chooser in choosers {
+            dlx.set_column_chooser(chooser);
+            let mut solutions: Vec<Vec<usize>> = dlx.solutions().collect();
+            solutions.iter_mut().for_each(|solution| solution.sort());
+            solutions.sort();
+            assert_eq!(solutions, sorted_expected);
+        }
+
+        // Clones get their own copy of the chooser
+        dlx.set_column_chooser(Box::new(FirstColumn));
+        let mut copy = dlx.clone();
+        assert_eq!(copy.count_solutions(), 4);
+        assert!(copy.stats().nodes > default_stats.nodes);
+    }
+
+    #[test]
+    fn test_dancing_links_column_chooser_ties() {
+        let _ = env_logger::try_init();
+        // Columns 1 and 2 both have the fewest rows
+        let dlx = DancingLinks::from_vecs(
+            &vec![
+                vec![1, 1, 0, 0],
+                vec![1, 0, 1, 0],
+                vec![1, 0, 0, 1],
+                vec![0, 0, 0, 1],
+            ],
+            4,
+        )
+        .unwrap();
+        let active = || ActiveColumns {
+            columns: &dlx.columns,
+            current: dlx.columns[ROOT].right as usize,
+        };
+        assert_eq!(active().count(), 4);
+        assert_eq!(Mrv.choose(active()), 1);
+        assert_eq!(FirstColumn.choose(active()), 0);
+        assert_eq!(MrvWithPriority::new(vec![0, 0, 5, 9]).choose(active()), 2);
+
+        let mut chooser = RandomizedMrv::new(42);
+        let choices: Vec<usize> = (0..100).map(|_| chooser.choose(active())).collect();
+        assert!(choices.iter().all(|&choice| choice == 1 || choice == 2));
+        assert!(choices.contains(&1) && choices.contains(&2));
+        let mut replay = RandomizedMrv::new(42);
+        assert!(
+            choices
+                .iter()
+                .all(|&choice| replay.choose(active()) == choice)
+        );
+    }
+
+    #[test]
+    fn test_dancing_links_randomize() {
+        let _ = env_logger::try_init();
+        let n = 6;
+        let sorted = |mut solutions: Vec<Vec<usize>>| {
+            solutions.iter_mut().for_each(|solution| solution.sort());
+            solutions.sort();
+            solutions
+        };
+        let mut dlx = n_queens(n);
+        let matrix = dlx.to_vecs();
+        let expected = sorted(dlx.solutions().collect());
+
+        let mut first_solutions = Vec::new();
+        for seed in 0..20 {
+            let mut dlx = n_queens(n);
+            dlx.randomize(seed);
+            assert_eq!(dlx.to_vecs(), matrix);
+            let solutions: Vec<Vec<usize>> = dlx.solutions().collect();
+            assert_eq!(sorted(solutions.clone()), expected);
+
+            // The same seed replays the same search
+            let mut replay = n_queens(n);
+            replay.randomize(seed);
+            assert_eq!(replay.solutions().collect::<Vec<_>>(), solutions);
+            first_solutions.push(sorted(vec![solutions[0].clone()]));
+        }
+        first_solutions.sort();
+        first_solutions.dedup();
+        assert!(first_solutions.len() > 1);
+    }
+
+    #[test]
+    fn test_dancing_links_colors() {
+        let _ = env_logger::try_init();
+        // Items p, q, r are primary and x, y are secondary; colors A = 1, B = 2.
+        //   p q x y:A
+        //   p r x:A y
+        //   p x:B
+        //   q x:A
+        //   r y:B
+        let mut dlx = DancingLinks::new();
+        for _ in 0..3 {
+            dlx.append_column();
+        }
+        for _ in 0..2 {
+            dlx.append_secondary_column();
+        }
+        dlx.append_colored_row(&[1, 1, 0, 1, 1], &[0, 0, 0, 0, 1])
+            .unwrap();
+        dlx.append_colored_row(&[1, 0, 1, 1, 1], &[0, 0, 0, 1, 0])
+            .unwrap();
+        dlx.append_colored_row(&[1, 0, 0, 1, 0], &[0, 0, 0, 2, 0])
+            .unwrap();
+        dlx.append_colored_row(&[0, 1, 0, 1, 0], &[0, 0, 0, 1, 0])
+            .unwrap();
+        dlx.append_colored_row(&[0, 0, 1, 0, 1], &[0, 0, 0, 0, 2])
+            .unwrap();
+
+        let solutions: Vec<Vec<usize>> = dlx
+            .solutions()
+            .map(|mut sol| {
+                sol.sort();
+                sol
+            })
+            .collect();
+        assert_eq!(solutions, vec![vec![1, 3]]);
+        assert_eq!(dlx.count_solutions(), 1);
+    }
+
+    #[test]
+    fn test_dancing_links_colors_conflict() {
+        let _ = env_logger::try_init();
+        // Rows 0 and 1 agree on column 2 but row 2 leaves it uncolored
+        let mut dlx = DancingLinks::new();
+        dlx.append_column();
+        dlx.append_column();
+        dlx.append_secondary_column();
+        dlx.append_colored_row(&[1, 0, 1], &[0, 0, 1]).unwrap();
+        dlx.append_colored_row(&[0, 1, 1], &[0, 0, 1]).unwrap();
+        dlx.append_colored_row(&[0, 1, 1], &[0, 0, 0]).unwrap();
+        dlx.append_colored_row(&[0, 1, 1], &[0, 0, 2]).unwrap();
+        assert_eq!(
+            dlx.solve().map(|mut sol| {
+                sol.sort();
+                sol
+            }),
+            Some(vec![0, 1])
+        );
+        assert_eq!(dlx.count_solutions(), 1);
+        assert_eq!(
+            dlx.to_vecs(),
+            vec![vec![1, 0, 1], vec![0, 1, 1], vec![0, 1, 1], vec![0, 1, 1]]
+        );
+    }
+
+    #[test]
+    fn test_dancing_links_multiplicities() {
+        let _ = env_logger::try_init();
+        // Column 0 must be covered exactly twice
+        let matrix = vec![vec![1, 1, 0], vec![1, 0, 1], vec![0, 1, 1], vec![1, 0, 0]];
+        let mut dlx = DancingLinks::new();
+        dlx.append_column_with_multiplicity(2, 2).unwrap();
+        dlx.append_column();
+        dlx.append_column();
+        for row in &matrix {
+            dlx.append_row(row).unwrap();
+        }
+        assert_eq!(dlx.solve(), Some(vec![0, 1]));
+        assert_eq!(dlx.count_solutions(), 1);
+
+        // Column 0 may be covered once or twice
+        let mut dlx = DancingLinks::new();
+        dlx.append_column_with_multiplicity(1, 2).unwrap();
+        dlx.append_column();
+        dlx.append_column();
+        for row in &matrix {
+            dlx.append_row(row).unwrap();
+        }
+        let mut solutions: Vec<Vec<usize>> = dlx
+            .solutions()
+            .map(|mut sol| {
+                sol.sort();
+                sol
+            })
+            .collect();
+        solutions.sort();
+        assert_eq!(solutions, vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(dlx.to_vecs(), matrix);
+    }
+
+    #[test]
+    fn test_dancing_links_multiplicities_match_brute_force() {
+        let _ = env_logger::try_init();
+        let mut rng = SplitMix64::new(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..200 {
+            let n_primary = 1 + rng.below(4);
+            let n_secondary = rng.below(3);
+            let n_cols = n_primary + n_secondary;
+            let n_rows = 1 + rng.below(9);
+            let bounds: Vec<(usize, usize)> = (0..n_primary)
+                .map(|_| {
+                    let lo = rng.below(3);
+                    (lo, (lo + rng.below(3)).max(1))
+                })
+                .collect();
+            let matrix: Vec<Vec<usize>> = (0..n_rows)
+                .map(|_| (0..n_cols).map(|_| rng.below(2)).collect())
+                .collect();
+
+            let mut expected = vec![];
+            for subset in 0..(1usize << n_rows) {
+                let rows: Vec<usize> = (0..n_rows).filter(|r| subset >> r & 1 == 1).collect();
+                // Rows covering no primary column are never selected
+                if rows
+                    .iter()
+                    .any(|&r| matrix[r][..n_primary].iter().all(|&v| v == 0))
+                {
+                    continue;
+                }
+                let counts: Vec<usize> = (0..n_cols)
+                    .map(|c| rows.iter().map(|&r| matrix[r][c]).sum())
+                    .collect();
+                let primary_ok = bounds
+                    .iter()
+                    .zip(&counts)
+                    .all(|(&(lo, hi), &count)| lo <= count && count <= hi);
+                let secondary_ok = counts[n_primary..].iter().all(|&count| count <= 1);
+                if primary_ok && secondary_ok {
+                    expected.push(rows);
+                }
+            }
+
+            expected.sort();
+
+            let mut dlx = DancingLinks::new();
+            for &(lo, hi) in &bounds {
+                dlx.append_column_with_multiplicity(lo, hi).unwrap();
+            }
+            for _ in 0..n_secondary {
+                dlx.append_secondary_column();
+            }
+            for row in &matrix {
+                dlx.append_row(row).unwrap();
+            }
+            let mut actual: Vec<Vec<usize>> = dlx
+                .solutions()
+                .map(|mut sol| {
+                    sol.sort();
+                    sol
+                })
+                .collect();
+            actual.sort();
+            assert_eq!(
+                actual, expected,
+                "matrix={:?}, bounds={:?}, n_secondary={}",
+                matrix, bounds, n_secondary
+            );
+            assert_eq!(dlx.count_solutions(), expected.len() as u128);
+            assert_eq!(dlx.to_vecs(), matrix);
+        }
+    }
+
+    #[test]
+    fn test_dancing_links_checkpoint() {
+        let _ = env_logger::try_init();
+        let mut rng = SplitMix64::new(0x6a09_e667_f3bc_c908);
+
+        for _ in 0..50 {
+            let n_primary = 1 + rng.below(4);
+            let n_secondary = rng.below(3);
+            let n_cols = n_primary + n_secondary;
+            let mut dlx = DancingLinks::new();
+            for _ in 0..n_primary {
+                let lo = rng.below(3);
+                dlx.append_column_with_multiplicity(lo, (lo + rng.below(3)).max(1))
+                    .unwrap();
+            }
+            for _ in 0..n_secondary {
+                dlx.append_secondary_column();
+            }
+            for _ in 0..(1 + rng.below(10)) {
+                let row: Vec<usize> = (0..n_cols).map(|_| rng.below(2)).collect();
+                let colors: Vec<usize> = (0..n_cols)
+                    .map(|i| {
+                        if i >= n_primary && row[i] != 0 {
+                            rng.below(3)
+                        } else {
+                            0
+                        }
+                    })
+                    .collect();
+                dlx.append_colored_row(&row, &colors).unwrap();
+            }
+            let expected: Vec<Vec<usize>> = dlx.solutions().collect();
+
+            // Stopping after any number of steps and resuming from a copy of the
+            // checkpoint finds the remaining solutions, and only those
+            for n_steps in 0..40 {
+                let mut found = Vec::new();
+                dlx.reset_search();
+                for _ in 0..n_steps {
+                    if let SearchStep::Solution(solution) = dlx.step(1) {
+                        found.push(solution);
+                    }
+                }
+                let json = serde_json::to_string(&dlx.checkpoint()).unwrap();
+                let checkpoint: Checkpoint = serde_json::from_str(&json).unwrap();
+                let mut resumed = DancingLinks::from_checkpoint(&checkpoint).unwrap();
+                assert_eq!(resumed.stats(), dlx.stats());
+                while let Some(solution) = resumed.next_solution() {
+                    found.push(solution);
+                }
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dancing_links_checkpoint_randomized() {
+        let _ = env_logger::try_init();
+        let n = 6;
+        let mut dlx = n_queens(n);
+        let matrix = dlx.to_vecs();
+        let mut expected: Vec<Vec<usize>> = dlx.solutions().collect();
+        expected.iter_mut().for_each(|solution| solution.sort());
+        expected.sort();
+
+        // The row order is restored, the column chooser is not
+        dlx.randomize(7);
+        let mut found = vec![dlx.next_solution().unwrap()];
+        let mut resumed = n_queens(n);
+        resumed.restore(&dlx.checkpoint()).unwrap();
+        while let Some(solution) = resumed.next_solution() {
+            found.push(solution);
+        }
+        found.iter_mut().for_each(|solution| solution.sort());
+        found.sort();
+        assert_eq!(found, expected);
+        resumed.reset_search();
+        assert_eq!(resumed.to_vecs(), matrix);
+    }
+
+    #[test]
+    fn test_dancing_links_restore_other_matrix() {
+        let _ = env_logger::try_init();
+        let mut dlx =
+            DancingLinks::from_vecs(&vec![vec![1, 0], vec![0, 1], vec![1, 1]], 2).unwrap();
+        let mut other = DancingLinks::from_vecs(&vec![vec![1, 0], vec![0, 1]], 2).unwrap();
+        let error = other.restore(&dlx.checkpoint()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(other.count_solutions(), 1);
+
+        let mut checkpoint = dlx.checkpoint();
+        checkpoint.path = vec![(0, Some(1))];
+        checkpoint.state = SearchState::Descending;
+        let error = dlx.restore(&checkpoint).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(dlx.count_solutions(), 2);
+    }
+
+    #[test]
+    fn test_dancing_links_enumerate_with_checkpoints() {
+        let _ = env_logger::try_init();
+        let n = 6;
+        let matrix: Vec<Vec<usize>> = (0..n)
+            .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+            .map(|(i, j)| (0..n).map(|k| (k == i || k == j) as usize).collect())
+            .collect();
+        let mut dlx = DancingLinks::from_vecs(&matrix, n).unwrap();
+        let expected: Vec<Vec<usize>> = dlx.solutions().collect();
+        assert_eq!(expected.len(), 15);
+
+        let path = std::env::temp_dir().join(format!(
+            "polyomino-tiling-enumeration-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mut found = Vec::new();
+        let mut n_runs = 0;
+        loop {
+            let options = SolveOptions {
+                max_nodes: Some(5 * (n_runs + 1)),
+                ..SolveOptions::default()
+            };
+            // Every run starts from a fresh matrix, as after a restart
+            let mut dlx = DancingLinks::from_vecs(&matrix, n).unwrap();
+            let outcome = dlx
+                .enumerate_with_checkpoints(&path, Duration::from_secs(3600), &options, |s| {
+                    found.push(s.to_vec())
+                })
+                .unwrap();
+            n_runs += 1;
+            match outcome {
+                Some(limit) => assert_eq!(limit, Limit::Nodes),
+                None => break,
+            }
+        }
+        assert!(n_runs > 1);
+        assert_eq!(found, expected);
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        assert!(checkpoint.is_exhausted());
+        assert_eq!(checkpoint.stats().solutions, 15);
+
+        // An exhausted enumeration is not started over
+        dlx.enumerate_with_checkpoints(&path, Duration::ZERO, &SolveOptions::default(), |_| {
+            unreachable!("solution found again")
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Runs killed right after handing out a solution resume past it
+        let mut found = Vec::new();
+        for _ in 0..=expected.len() {
+            let mut dlx = DancingLinks::from_vecs(&matrix, n).unwrap();
+            let run = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                dlx.enumerate_with_checkpoints(
+                    &path,
+                    Duration::from_secs(3600),
+                    &SolveOptions::default(),
+                    |s| {
+                        found.push(s.to_vec());
+                        panic!("killed");
+                    },
+                )
+            }));
+            if let Ok(outcome) = run {
+                assert_eq!(outcome.unwrap(), None);
+                break;
+            }
+        }
+        assert_eq!(found, expected);
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,161 @@
+use std::error::Error;
+use std::fmt;
+
+/// Invalid input given while building a `DancingLinks` matrix, or a matrix that a
+/// search does not support.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DlxError {
+    /// A dense row does not have one entry per column
+    RowLength { expected: usize, actual: usize },
+    /// The colors of a row do not have one entry per column
+    ColorsLength { expected: usize, actual: usize },
+    /// A column index past the last column
+    ColumnOutOfRange { column: usize, n_cols: usize },
+    /// A row index past the last row
+    RowOutOfRange { row: usize, n_rows: usize },
+    /// A sparse row lists its columns out of order or more than once
+    ColumnsNotIncreasing,
+    /// A column must be covered at least `lo` and at most `hi` times, with `lo > hi`
+    /// or `hi == 0`
+    InvalidMultiplicity { lo: usize, hi: usize },
+    /// A color is given for a column the row does not cover
+    ColorOnUncoveredColumn { column: usize },
+    /// A color is given for a primary column, only secondary ones can be colored
+    ColorOnPrimaryColumn { column: usize },
+    /// A color is too large to be told apart from internal markers
+    ColorTooLarge { color: usize },
+    /// The memoized search only supports primary columns covered exactly once and
+    /// uncolored rows
+    MemoizationUnsupported,
+    /// The CNF encoding only supports primary columns covered exactly once
+    CnfUnsupported,
+    /// A row to force covers no primary column, so the search never selects it
+    RowWithoutPrimaryColumn { row: usize },
+    /// A row to force can not be selected along with the rows forced before it
+    ForcedRowConflict { row: usize },
+    /// A row was removed from the matrix
+    RowRemoved { row: usize },
+    /// A column was removed from the matrix
+    ColumnRemoved { column: usize },
+    /// A column to remove still has rows
+    ColumnNotEmpty { column: usize },
+}
+
+impl fmt::Display for DlxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DlxError::RowLength { expected, actual } => write!(
+                f,
+                "row has {} entries but the matrix has {} columns",
+                actual, expected
+            ),
+            DlxError::ColorsLength { expected, actual } => write!(
+                f,
+                "row has {} colors but the matrix has {} columns",
+                actual, expected
+            ),
+            DlxError::ColumnOutOfRange { column, n_cols } => write!(
+                f,
+                "column {} is out of range, the matrix has {} columns",
+                column, n_cols
+            ),
+            DlxError::RowOutOfRange { row, n_rows } => write!(
+                f,
+                "row {} is out of range, the matrix has {} rows",
+                row, n_rows
+            ),
+            DlxError::ColumnsNotIncreasing => {
+                write!(f, "column indices of a row must be strictly increasing")
+            }
+            DlxError::InvalidMultiplicity { lo, hi } => {
+                write!(f, "invalid multiplicity for column: lo={}, hi={}", lo, hi)
+            }
+            DlxError::ColorOnUncoveredColumn { column } => write!(
+                f,
+                "color given for column {} which the row does not cover",
+                column
+            ),
+            DlxError::ColorOnPrimaryColumn { column } => write!(
+                f,
+                "only secondary columns can be colored, column {} is primary",
+                column
+            ),
+            DlxError::ColorTooLarge { color } => write!(f, "color {} is too large", color),
+            DlxError::MemoizationUnsupported => write!(
+                f,
+                "memoized search needs every primary column covered exactly once and no colors"
+            ),
+            DlxError::CnfUnsupported => write!(
+                f,
+                "CNF encoding needs every primary column covered exactly once"
+            ),
+            DlxError::RowWithoutPrimaryColumn { row } => {
+                write!(f, "row {} covers no primary column", row)
+            }
+            DlxError::ForcedRowConflict { row } => {
+                write!(f, "row {} conflicts with the rows forced before it", row)
+            }
+            DlxError::RowRemoved { row } => write!(f, "row {} was removed", row),
+            DlxError::ColumnRemoved { column } => write!(f, "column {} was removed", column),
+            DlxError::ColumnNotEmpty { column } => {
+                write!(f, "column {} still has rows and can not be removed", column)
+            }
+        }
+    }
+}
+
+impl Error for DlxError {}
+
+/// Invalid input given to a `PolyominoTiling`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TilingError {
+    /// The rows of a piece do not all have the same length
+    RaggedPiece { piece: usize },
+    /// A piece must be used at least `min` and at most `max` times, with `min > max`
+    /// or `max == 0`
+    InvalidQuantity {
+        piece: usize,
+        min: usize,
+        max: usize,
+    },
+    /// A piece index past the last piece
+    PieceOutOfRange { piece: usize, n_pieces: usize },
+    /// The exact cover matrix of the tiling could not be built
+    Matrix(DlxError),
+}
+
+impl fmt::Display for TilingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilingError::RaggedPiece { piece } => {
+                write!(f, "rows of piece {} do not all have the same length", piece)
+            }
+            TilingError::InvalidQuantity { piece, min, max } => write!(
+                f,
+                "invalid quantity for piece {}: min={}, max={}",
+                piece, min, max
+            ),
+            TilingError::PieceOutOfRange { piece, n_pieces } => write!(
+                f,
+                "piece {} is out of range, the tiling has {} pieces",
+                piece, n_pieces
+            ),
+            TilingError::Matrix(error) => write!(f, "invalid exact cover matrix: {}", error),
+        }
+    }
+}
+
+impl Error for TilingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TilingError::Matrix(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<DlxError> for TilingError {
+    fn from(error: DlxError) -> Self {
+        TilingError::Matrix(error)
+    }
+}
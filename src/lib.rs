@@ -1,11 +1,27 @@
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
-mod dancing_links;
-mod polyomino_tiling;
-mod pretty;
-
-use polyomino_tiling::{PolyominoTiling, piece_placements_to_matrix_of_piece_ids};
+pub mod bitboard;
+pub mod checkpoint;
+pub mod cnf;
+pub mod column_chooser;
+pub mod dancing_links;
+pub mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod parallel;
+pub mod polyomino_tiling;
+pub mod pretty;
+pub mod progress;
+pub mod pruner;
+mod rng;
+pub mod search_stats;
+pub mod solve_options;
+pub mod trace;
+pub mod zdd;
+
+use polyomino_tiling::{IncrementalTiling, piece_placements_to_matrix_of_piece_ids};
+use solve_options::{SolveOptions, SolveOutcome};
+use web_time::Duration;
 
 #[wasm_bindgen]
 extern "C" {
@@ -23,11 +39,13 @@ pub struct PolyominoSolution {
     pub placement: Vec<Vec<usize>>,
 }
 
+/// Solver behind the webapp. Its exact cover matrix is kept between solves, so
+/// adding or removing a piece only touches the rows of that piece.
 #[wasm_bindgen]
 pub struct PolyominoSolver {
     width: usize,
     height: usize,
-    polyominoes: Vec<Vec<Vec<usize>>>,
+    tiling: IncrementalTiling,
 }
 
 #[wasm_bindgen]
@@ -42,36 +60,42 @@ impl PolyominoSolver {
         PolyominoSolver {
             width,
             height,
-            polyominoes: Vec::new(),
+            tiling: IncrementalTiling::new(width, height),
         }
     }
 
+    /// Adds a piece, whose id in solutions is the number of pieces before it. A piece
+    /// whose rows do not all have the same length throws an error.
     #[wasm_bindgen]
     pub fn add_polyomino(&mut self, polyomino_js: JsValue) -> Result<(), JsValue> {
         let polyomino: Vec<Vec<usize>> = serde_wasm_bindgen::from_value(polyomino_js)?;
         console_log!("Adding polyomino with {} rows", polyomino.len());
-        self.polyominoes.push(polyomino);
+        self.tiling.add_piece(polyomino).map_err(JsError::from)?;
+        Ok(())
+    }
+
+    /// Removes the piece with id `index`, the ids of the pieces after it going down by
+    /// one. An index past the last piece throws an error.
+    #[wasm_bindgen]
+    pub fn remove_polyomino(&mut self, index: usize) -> Result<(), JsValue> {
+        console_log!("Removing polyomino {}", index);
+        self.tiling.remove_piece(index).map_err(JsError::from)?;
         Ok(())
     }
 
+    /// Makes the following solves search in a random order drawn from `seed`, so
+    /// that each seed gives its own solution. `null` restores the default order.
     #[wasm_bindgen]
-    pub fn solve(&self) -> Result<JsValue, JsValue> {
+    pub fn set_random_seed(&mut self, seed: Option<u32>) {
+        self.tiling.set_random_seed(seed.map(u64::from));
+    }
+
+    /// Finds a tiling, or returns `null` when there is none.
+    #[wasm_bindgen]
+    pub fn solve(&mut self) -> Result<JsValue, JsValue> {
         console_log!("Solving polyomino tiling problem...");
-        let tiling = PolyominoTiling::new(self.width, self.height, self.polyominoes.clone());
-        let solution = tiling.solve();
-
-        match solution {
-            Some(sol) => {
-                console_log!("Found solution with {} pieces", sol.len());
-                let js_solution: Vec<PolyominoSolution> = sol
-                    .into_iter()
-                    .map(|(piece_id, placement)| PolyominoSolution {
-                        piece_id,
-                        placement,
-                    })
-                    .collect();
-                Ok(serde_wasm_bindgen::to_value(&js_solution)?)
-            }
+        match self.tiling.solve() {
+            Some(sol) => solution_to_js(sol),
             None => {
                 console_log!("No solution found");
                 Ok(JsValue::NULL)
@@ -79,6 +103,37 @@ impl PolyominoSolver {
         }
     }
 
+    /// Like `solve`, but gives up after visiting `max_nodes` search nodes or after
+    /// `time_limit_ms` milliseconds, in which case an error is thrown.
+    #[wasm_bindgen]
+    pub fn solve_with_limits(
+        &mut self,
+        max_nodes: Option<f64>,
+        time_limit_ms: Option<f64>,
+    ) -> Result<JsValue, JsValue> {
+        console_log!("Solving polyomino tiling problem with limits...");
+        let mut options = SolveOptions {
+            max_nodes: max_nodes.map(|max_nodes| max_nodes as u64),
+            ..Default::default()
+        };
+        if let Some(time_limit_ms) = time_limit_ms {
+            options = options.with_time_limit(Duration::from_secs_f64(time_limit_ms / 1000.0));
+        }
+
+        match self.tiling.solve_with_options(&options) {
+            SolveOutcome::Solved(sol) => solution_to_js(sol),
+            SolveOutcome::Unsolvable => {
+                console_log!("No solution found");
+                Ok(JsValue::NULL)
+            }
+            SolveOutcome::LimitReached { limit, nodes } => Err(JsError::new(&format!(
+                "Search stopped by the {:?} limit after {} nodes",
+                limit, nodes
+            ))
+            .into()),
+        }
+    }
+
     #[wasm_bindgen]
     pub fn get_solution_matrix(&self, solution_js: JsValue) -> Result<JsValue, JsValue> {
         if solution_js.is_null() {
@@ -97,6 +152,18 @@ impl PolyominoSolver {
     }
 }
 
+fn solution_to_js(solution: Vec<(usize, Vec<Vec<usize>>)>) -> Result<JsValue, JsValue> {
+    console_log!("Found solution with {} pieces", solution.len());
+    let js_solution: Vec<PolyominoSolution> = solution
+        .into_iter()
+        .map(|(piece_id, placement)| PolyominoSolution {
+            piece_id,
+            placement,
+        })
+        .collect();
+    Ok(serde_wasm_bindgen::to_value(&js_solution)?)
+}
+
 // Predefined polyominoes from the original code
 #[wasm_bindgen]
 pub fn get_predefined_polyomino(name: &str) -> Result<JsValue, JsValue> {
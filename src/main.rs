@@ -1,17 +1,59 @@
-mod dancing_links;
-mod polyomino_tiling;
-mod pretty;
-use polyomino_tiling::{PolyominoTiling, piece_placements_to_matrix_of_piece_ids};
-use pretty::str_to_matrix;
+use polyomino_tiling::polyomino_tiling::{
+    PolyominoTiling, piece_placements_to_matrix_of_piece_ids,
+};
+use polyomino_tiling::pretty::str_to_matrix;
+use polyomino_tiling::progress::Progress;
+use polyomino_tiling::search_stats::SearchStats;
+use polyomino_tiling::solve_options::{SolveOptions, SolveOutcome};
 use std::collections::HashMap;
+use std::time::Duration;
 
 fn main() {
     env_logger::init();
     let args: Vec<String> = std::env::args().collect();
-    let mino_names: Vec<&str> = args[1..].iter().map(|s| s.as_str()).collect();
+    let mut mino_names: Vec<&str> = Vec::new();
+    let mut options = SolveOptions::default();
+    let mut show_progress = false;
+    let mut show_stats = false;
+    for arg in &args[1..] {
+        if let Some(value) = arg.strip_prefix("--max-nodes=") {
+            match value.parse() {
+                Ok(max_nodes) => options.max_nodes = Some(max_nodes),
+                Err(_) => {
+                    eprintln!("Error: Invalid node limit: {}", value);
+                    return;
+                }
+            }
+        } else if let Some(value) = arg.strip_prefix("--time-limit=") {
+            match value.parse() {
+                Ok(seconds) => options = options.with_time_limit(Duration::from_secs_f64(seconds)),
+                Err(_) => {
+                    eprintln!("Error: Invalid time limit in seconds: {}", value);
+                    return;
+                }
+            }
+        } else if arg == "--progress" {
+            show_progress = true;
+        } else if arg == "--stats" {
+            show_stats = true;
+        } else {
+            mino_names.push(arg);
+        }
+    }
 
-    match katamino(mino_names) {
-        Ok(solution) => {
+    match katamino(mino_names, &options, show_progress) {
+        Ok((outcome, stats)) => {
+            if show_stats {
+                eprintln!("{}", stats);
+            }
+            if let SolveOutcome::LimitReached { limit, nodes } = outcome {
+                eprintln!(
+                    "Error: Search stopped by the {:?} limit after {} nodes",
+                    limit, nodes
+                );
+                return;
+            }
+            let solution = outcome.solution();
             let colors: HashMap<usize, (u8, u8, u8)> = HashMap::from([
                 (0, (255, 0, 0)),      // Red
                 (1, (0, 255, 0)),      // Green
@@ -29,14 +71,14 @@ fn main() {
             let solution_pretty = make_solution_pretty(&solution, colors);
             println!("{}", solution_pretty);
         }
-        Err(_) => {
-            eprintln!("Error: Invalid polyomino name");
+        Err(message) => {
+            eprintln!("Error: {}", message);
         }
     }
 }
 
 type Board = Vec<Vec<usize>>;
-type Solution = Option<Vec<(usize, Board)>>;
+type Solution = SolveOutcome<Vec<(usize, Board)>>;
 
 fn make_solution_pretty(
     solution: &Option<Vec<(usize, Board)>>,
@@ -59,12 +101,12 @@ fn make_solution_pretty(
     let mut s = String::new();
     for row_data in placement_matrix.iter() {
         for &piece_id in row_data.iter() {
-            let _s: String = if piece_id.is_none() {
-                color_str(".", 100, 100, 100)
-            } else if let Some(&(r, g, b)) = colors.get(&piece_id.unwrap()) {
-                color_str("#", r, g, b)
-            } else {
-                color_str("#", 255, 255, 255) // Unknown piece
+            let _s: String = match piece_id {
+                None => color_str(".", 100, 100, 100),
+                Some(piece_id) => match colors.get(&piece_id) {
+                    Some(&(r, g, b)) => color_str("#", r, g, b),
+                    None => color_str("#", 255, 255, 255), // Unknown piece
+                },
             };
             s.push_str(&_s);
         }
@@ -74,11 +116,35 @@ fn make_solution_pretty(
     s
 }
 
+/// Number of updates between two progress lines
+const PROGRESS_INTERVAL: u64 = 1_000_000;
+
+fn print_progress(progress: &Progress) {
+    let top_levels: Vec<String> = progress
+        .branches
+        .iter()
+        .take(3)
+        .map(|branch| branch.to_string())
+        .collect();
+    eprintln!(
+        "{:5.1}% nodes={} solutions={} depth={} [{}]",
+        progress.estimated_completion() * 100.0,
+        progress.nodes,
+        progress.solutions,
+        progress.depth,
+        top_levels.join(", ")
+    );
+}
+
 fn color_str(text: &str, r: u8, g: u8, b: u8) -> String {
     format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, text)
 }
 
-fn katamino(mino_names: Vec<&str>) -> Result<Solution, ()> {
+fn katamino(
+    mino_names: Vec<&str>,
+    options: &SolveOptions,
+    show_progress: bool,
+) -> Result<(Solution, SearchStats), String> {
     let mino_dict = HashMap::from([
         (
             "L",
@@ -174,16 +240,24 @@ fn katamino(mino_names: Vec<&str>) -> Result<Solution, ()> {
     ]);
     let minos: Vec<Vec<Vec<usize>>> = mino_names
         .iter()
-        .map(|&name| mino_dict.get(name).ok_or(()))
-        .collect::<Result<Vec<_>, ()>>()?
+        .map(|&name| {
+            mino_dict
+                .get(name)
+                .ok_or_else(|| format!("Invalid polyomino name: {}", name))
+        })
+        .collect::<Result<Vec<_>, String>>()?
         .iter()
         .map(|&v| v.clone())
         .collect();
 
-    let problem = PolyominoTiling::new(minos.len(), 5, minos);
-    let solution = problem.solve();
-
-    Ok(solution)
+    let names = mino_names.iter().map(|name| name.to_string()).collect();
+    let mut problem = PolyominoTiling::new(minos.len(), 5, minos).with_piece_names(names);
+    if show_progress {
+        problem = problem.with_progress_callback(PROGRESS_INTERVAL, print_progress);
+    }
+    problem
+        .solve_with_stats(options)
+        .map_err(|error| error.to_string())
 }
 
 #[cfg(test)]
@@ -259,7 +333,7 @@ mod tests {
         ];
 
         let tiling = PolyominoTiling::new(8, 5, polyominoes);
-        let solution = tiling.solve();
+        let solution = tiling.solve().unwrap();
         let colors: HashMap<usize, (u8, u8, u8)> = HashMap::from([
             (0, (255, 0, 0)),     // Red
             (1, (0, 255, 0)),     // Green
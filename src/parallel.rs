@@ -0,0 +1,229 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use log::info;
+
+use crate::dancing_links::{DancingLinks, SearchPath};
+
+/// Deepest level at which the search tree is split into subproblems
+const MAX_SPLIT_DEPTH: usize = 8;
+/// Number of subproblems aimed for, so that threads finishing early can pick up
+/// more work. It does not depend on the number of threads, which thus never
+/// changes the results.
+const MIN_SUBPROBLEMS: usize = 256;
+
+/// Runs a search on several threads.
+///
+/// The top levels of the search tree are split into independent subproblems, each
+/// explored by a worker thread on a fresh copy of the matrix. Results are merged
+/// in the order of the subproblems, so they are the same whatever the number of
+/// threads, even with a randomized column chooser. They are also those of the
+/// search on a single `DancingLinks`, unless its chooser keeps state from one node
+/// to the next like `RandomizedMrv` does.
+#[derive(Clone, Debug)]
+pub struct ParallelSearch {
+    n_threads: usize,
+    split_depth: Option<usize>,
+}
+
+impl ParallelSearch {
+    /// Uses as many threads as the machine can run in parallel.
+    pub fn new() -> Self {
+        let n_threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Self {
+            n_threads,
+            split_depth: None,
+        }
+    }
+
+    /// Uses `n_threads` threads, 0 being taken as 1.
+    pub fn with_threads(mut self, n_threads: usize) -> Self {
+        self.n_threads = n_threads.max(1);
+        self
+    }
+
+    /// Splits the search tree at the given depth, instead of the shallowest one
+    /// giving enough subproblems to keep every thread busy.
+    pub fn with_split_depth(mut self, depth: usize) -> Self {
+        self.split_depth = Some(depth);
+        self
+    }
+
+    /// Counts every exact cover of `dlx`, see `DancingLinks::count_solutions`.
+    pub fn count_solutions(&self, dlx: &DancingLinks) -> u128 {
+        self.run(dlx, |dlx| {
+            let mut count: u128 = 0;
+            while dlx.next_solution().is_some() {
+                count += 1;
+            }
+            count
+        })
+        .into_iter()
+        .sum()
+    }
+
+    /// Returns every exact cover of `dlx`, in the order `DancingLinks::solutions`
+    /// yields them unless the column chooser keeps state, see `ParallelSearch`.
+    pub fn solutions(&self, dlx: &DancingLinks) -> Vec<Vec<usize>> {
+        self.run(dlx, |dlx| {
+            let mut solutions = Vec::new();
+            while let Some(solution) = dlx.next_solution() {
+                solutions.push(solution);
+            }
+            solutions
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Runs `search` below every subproblem of `dlx` and returns the results in the
+    /// order of the subproblems.
+    fn run<T: Send>(
+        &self,
+        dlx: &DancingLinks,
+        search: impl Fn(&mut DancingLinks) -> T + Sync,
+    ) -> Vec<T> {
+        let subproblems = self.split(dlx);
+        info!(
+            "search split into {} subproblems for {} threads",
+            subproblems.len(),
+            self.n_threads
+        );
+
+        let next_subproblem = AtomicUsize::new(0);
+        let results: Vec<Mutex<Option<T>>> = subproblems.iter().map(|_| Mutex::new(None)).collect();
+        thread::scope(|scope| {
+            for _ in 0..self.n_threads.min(subproblems.len()) {
+                let template = dlx.clone();
+                let (subproblems, next_subproblem, results, search) =
+                    (&subproblems, &next_subproblem, &results, &search);
+                scope.spawn(move || {
+                    loop {
+                        let i = next_subproblem.fetch_add(1, Ordering::Relaxed);
+                        let Some(subproblem) = subproblems.get(i) else {
+                            break;
+                        };
+                        // The column chooser must not carry over from the last subproblem
+                        let mut worker = template.clone();
+                        worker.enter(subproblem);
+                        let result = search(&mut worker);
+                        *results[i].lock().unwrap() = Some(result);
+                    }
+                });
+            }
+        });
+        results
+            .into_iter()
+            .map(|result| result.into_inner().unwrap().unwrap())
+            .collect()
+    }
+
+    fn split(&self, dlx: &DancingLinks) -> Vec<SearchPath> {
+        let mut dlx = dlx.clone();
+        if let Some(depth) = self.split_depth {
+            return dlx.split(depth);
+        }
+        let mut subproblems = dlx.split(0);
+        for depth in 1..=MAX_SPLIT_DEPTH {
+            if subproblems.len() >= MIN_SUBPROBLEMS {
+                break;
+            }
+            subproblems = dlx.split(depth);
+        }
+        subproblems
+    }
+}
+
+impl Default for ParallelSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dancing_links::n_queens;
+    use crate::rng::SplitMix64;
+
+    #[test]
+    fn test_parallel_search_n_queens() {
+        let _ = env_logger::try_init();
+        let n = 8;
+        let mut dlx = n_queens(n);
+        let matrix = dlx.to_vecs();
+        let expected: Vec<Vec<usize>> = dlx.solutions().collect();
+        assert_eq!(expected.len(), 92);
+
+        for n_threads in [1, 2, 4] {
+            let search = ParallelSearch::new().with_threads(n_threads);
+            assert_eq!(search.count_solutions(&dlx), 92);
+            assert_eq!(search.solutions(&dlx), expected);
+            for depth in [0, 1, 3, 20] {
+                let search = search.clone().with_split_depth(depth);
+                assert_eq!(search.solutions(&dlx), expected);
+            }
+        }
+        assert_eq!(dlx.to_vecs(), matrix);
+    }
+
+    #[test]
+    fn test_parallel_search_randomized() {
+        let _ = env_logger::try_init();
+        let n = 8;
+        let mut dlx = n_queens(n);
+        let sorted = |mut solutions: Vec<Vec<usize>>| {
+            solutions.iter_mut().for_each(|solution| solution.sort());
+            solutions.sort();
+            solutions
+        };
+        let expected = sorted(dlx.solutions().collect());
+
+        for seed in 0..4 {
+            dlx.randomize(seed);
+            let single = ParallelSearch::new().with_threads(1).solutions(&dlx);
+            assert_eq!(sorted(single.clone()), expected);
+            for n_threads in [0, 2, 4] {
+                let search = ParallelSearch::new().with_threads(n_threads);
+                assert_eq!(search.solutions(&dlx), single);
+                assert_eq!(search.count_solutions(&dlx), 92);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_search_matches_sequential() {
+        let _ = env_logger::try_init();
+        let mut rng = SplitMix64::new(0x9e37_79b9_7f4a_7c15);
+
+        for _ in 0..100 {
+            let n_primary = 1 + rng.below(4);
+            let n_secondary = rng.below(3);
+            let n_cols = n_primary + n_secondary;
+            let mut dlx = DancingLinks::new();
+            for _ in 0..n_primary {
+                let lo = rng.below(3);
+                dlx.append_column_with_multiplicity(lo, (lo + rng.below(3)).max(1))
+                    .unwrap();
+            }
+            for _ in 0..n_secondary {
+                dlx.append_secondary_column();
+            }
+            for _ in 0..(1 + rng.below(12)) {
+                let row: Vec<usize> = (0..n_cols).map(|_| rng.below(2)).collect();
+                dlx.append_row(&row).unwrap();
+            }
+
+            let expected: Vec<Vec<usize>> = dlx.solutions().collect();
+            for depth in 0..4 {
+                let search = ParallelSearch::new()
+                    .with_threads(3)
+                    .with_split_depth(depth);
+                assert_eq!(search.solutions(&dlx), expected);
+                assert_eq!(search.count_solutions(&dlx), expected.len() as u128);
+            }
+        }
+    }
+}
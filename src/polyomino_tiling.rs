@@ -1,11 +1,67 @@
 use log::{debug, info};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use web_time::Duration;
 
-use crate::dancing_links::DancingLinks;
+use crate::bitboard::Bitboard;
+use crate::cnf::{AtMostOne, Cnf};
+use crate::column_chooser::{ColumnChooser, MrvWithPriority};
+use crate::dancing_links::{ActiveColumns, DancingLinks};
+use crate::error::{DlxError, TilingError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::parallel::ParallelSearch;
+use crate::progress::{Progress, ProgressReporter};
+use crate::pruner::Pruner;
+use crate::search_stats::SearchStats;
+use crate::solve_options::{Limit, SolveOptions, SolveOutcome};
+use crate::trace::SearchTrace;
+
+/// Piece ids along with their placement on the board, as returned by `solve`
+type PiecePlacements = Vec<(usize, Vec<Vec<usize>>)>;
+/// Rows of the exact cover problem of a tiling, as column indices, and its matrix
+type ExactCoverProblem = (Vec<Vec<usize>>, DancingLinks);
+/// Rows of the exact cover problem of a tiling and the solver picked for it
+type SolverProblem = (Vec<Vec<usize>>, Solver);
 
 pub struct PolyominoTiling {
     pub width: usize,
     pub height: usize,
     pub polyominoes: Vec<Vec<Vec<usize>>>,
+    /// Inclusive range of copies to use for each piece, `(1, 1)` when not given
+    piece_quantities: Vec<(usize, usize)>,
+    /// Names of the pieces in labels, their index when not given
+    piece_names: Vec<String>,
+    progress: Option<ProgressReporter>,
+    column_chooser: Option<Box<dyn ColumnChooser>>,
+    pruner: Option<Box<dyn Pruner>>,
+    random_seed: Option<u64>,
+    backend: Backend,
+}
+
+/// Exact cover solver used by a `PolyominoTiling` to solve and count tilings.
+///
+/// It runs `solve`, `solutions` and `count_solutions` and their variants. Both
+/// solvers find the same tilings, not necessarily in the same order nor with their
+/// pieces in the same order. The other searches always run on `DancingLinks`, so
+/// their tilings only match those of `solutions` with `Backend::DancingLinks`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// `Bitboard` when the board has at most 128 cells, every piece has a cell and no
+    /// option set on the tiling needs `DancingLinks`, which is used otherwise
+    #[default]
+    Auto,
+    /// Always `DancingLinks`
+    DancingLinks,
+}
+
+/// Kind of column the search branches on first when several have the fewest rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnPreference {
+    /// Place the pieces that fit in the fewest ways first
+    Pieces,
+    /// Fill the cells that can be covered in the fewest ways first
+    Cells,
 }
 
 impl PolyominoTiling {
@@ -14,71 +70,432 @@ impl PolyominoTiling {
             width,
             height,
             polyominoes,
+            piece_quantities: Vec::new(),
+            piece_names: Vec::new(),
+            progress: None,
+            column_chooser: None,
+            pruner: None,
+            random_seed: None,
+            backend: Backend::Auto,
         }
     }
 
-    fn encode_into_exact_cover_problem_matrix(&self) -> Vec<Vec<usize>> {
+    /// Lets piece `i` be used between `quantities[i].0` and `quantities[i].1` times.
+    ///
+    /// Copies of the same piece are interchangeable, so tilings that only differ by
+    /// swapping them are enumerated once.
+    pub fn with_piece_quantities(mut self, quantities: Vec<(usize, usize)>) -> Self {
+        self.piece_quantities = quantities;
+        self
+    }
+
+    /// Names the pieces in the labels of the exact cover problem, e.g. `F` for a
+    /// piece's column and `F@r1c2/rot90` for one of its placements, the cells'
+    /// columns being named like `r1c2`. Pieces past the end of `names` are named
+    /// after their index.
+    pub fn with_piece_names(mut self, names: Vec<String>) -> Self {
+        self.piece_names = names;
+        self
+    }
+
+    /// Calls `callback` with the state of every search run on this tiling, see
+    /// `DancingLinks::set_progress_callback`.
+    pub fn with_progress_callback(
+        mut self,
+        interval: u64,
+        callback: impl FnMut(&Progress) + Send + 'static,
+    ) -> Self {
+        self.progress = Some(ProgressReporter::new(interval, callback));
+        self
+    }
+
+    /// Sets the strategy picking the column to branch on, see
+    /// `DancingLinks::set_column_chooser`.
+    ///
+    /// Columns `0..polyominoes.len()` stand for the pieces, and the following ones
+    /// for the cells of the board, row by row.
+    pub fn with_column_chooser(mut self, chooser: Box<dyn ColumnChooser>) -> Self {
+        self.column_chooser = Some(chooser);
+        self
+    }
+
+    /// Branches on the column with the fewest rows, preferring the given kind on ties.
+    pub fn with_column_preference(self, preference: ColumnPreference) -> Self {
         let n_pieces = self.polyominoes.len();
-        let exact_cover_problem_matrix: Vec<Vec<usize>> = self
+        let n_cells = self.width * self.height;
+        let (piece_priority, cell_priority) = match preference {
+            ColumnPreference::Pieces => (1, 0),
+            ColumnPreference::Cells => (0, 1),
+        };
+        let priorities = [vec![piece_priority; n_pieces], vec![cell_priority; n_cells]].concat();
+        self.with_column_chooser(Box::new(MrvWithPriority::new(priorities)))
+    }
+
+    /// Sets the check run after each placement the search tries, see
+    /// `DancingLinks::set_pruner`. Columns are laid out as for `with_column_chooser`.
+    pub fn with_pruner(mut self, pruner: Box<dyn Pruner>) -> Self {
+        self.pruner = Some(pruner);
+        self
+    }
+
+    /// Gives up on partial tilings that leave a region of empty cells the pieces left
+    /// can not fill, see `RegionPruner`.
+    ///
+    /// This only holds for pieces whose cells are connected by their sides, so
+    /// nothing is pruned if one of them is not.
+    pub fn with_region_pruning(self) -> Self {
+        if !self
             .polyominoes
-            .clone()
-            .into_iter()
-            .enumerate()
-            .flat_map(|(piece_id, polyomino)| {
-                let placements = get_all_placements(&polyomino, self.width, self.height);
-                let placements_flattened: Vec<Vec<usize>> = placements
+            .iter()
+            .all(|polyomino| is_connected(polyomino))
+        {
+            info!("some pieces are not connected, region pruning is disabled");
+            return self;
+        }
+        let piece_sizes = self
+            .polyominoes
+            .iter()
+            .map(|polyomino| {
+                polyomino
                     .iter()
-                    .map(|placement| flatten(placement))
-                    .collect();
-                let one_hot_encoded_piece_id = encode_one_hot(n_pieces, piece_id);
-                let placements_with_piece_id: Vec<Vec<usize>> = placements_flattened
-                    .into_iter()
-                    .map(|placement| {
-                        let mut row = one_hot_encoded_piece_id.clone();
-                        row.extend(placement);
-                        row
-                    })
-                    .collect();
-                placements_with_piece_id
+                    .flatten()
+                    .filter(|&&cell| cell != 0)
+                    .count()
             })
             .collect();
+        let pruner = RegionPruner::new(self.width, self.height, piece_sizes);
+        self.with_pruner(Box::new(pruner))
+    }
+
+    /// Searches in a random order drawn from `seed`, see `DancingLinks::randomize`.
+    ///
+    /// `solve` then returns a tiling that depends on the seed, which is useful to
+    /// generate puzzles. A chooser set with `with_column_chooser` is kept, and only
+    /// the order of the placements is randomized.
+    pub fn with_random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
 
-        exact_cover_problem_matrix
+    /// Sets the solver used by `solve`, `solutions` and `count_solutions` and their
+    /// variants, see `Backend`. Those of a tiling with a progress callback, column
+    /// chooser, pruner or random seed always run on `DancingLinks`.
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    fn piece_quantity(&self, piece_id: usize) -> (usize, usize) {
+        self.piece_quantities
+            .get(piece_id)
+            .copied()
+            .unwrap_or((1, 1))
+    }
+
+    fn piece_name(&self, piece_id: usize) -> String {
+        self.piece_names
+            .get(piece_id)
+            .cloned()
+            .unwrap_or_else(|| piece_id.to_string())
+    }
+
+    /// Encodes every placement of every piece as a row of the exact cover problem,
+    /// given by the columns it covers: the piece's, then those of its cells.
+    ///
+    /// Each row comes with a label such as `F@r1c2/rot90`: the piece, the top-left
+    /// corner of the cells it covers and its orientation.
+    fn encode_into_exact_cover_problem_rows(&self) -> (Vec<Vec<usize>>, Vec<String>) {
+        let n_pieces = self.polyominoes.len();
+        let mut rows = Vec::new();
+        let mut labels = Vec::new();
+        for (piece_id, polyomino) in self.polyominoes.iter().enumerate() {
+            let name = self.piece_name(piece_id);
+            for (orientation, cells) in get_all_placements(polyomino, self.width, self.height) {
+                let top = cells.iter().map(|cell| cell / self.width).min();
+                let left = cells.iter().map(|cell| cell % self.width).min();
+                labels.push(format!(
+                    "{}@r{}c{}/{}",
+                    name,
+                    top.unwrap_or(0),
+                    left.unwrap_or(0),
+                    orientation_name(orientation)
+                ));
+                let mut row = Vec::with_capacity(cells.len() + 1);
+                row.push(piece_id);
+                row.extend(cells.iter().map(|cell| n_pieces + cell));
+                rows.push(row);
+            }
+        }
+        (rows, labels)
     }
 
     fn decode_dlx_solution(
         &self,
-        matrix: &[Vec<usize>],
-        dlx_solution: &Option<Vec<usize>>,
-    ) -> Option<Vec<(usize, Vec<Vec<usize>>)>> {
-        if (dlx_solution).is_none() {
-            return None;
-        }
-        let dlx_solution = (dlx_solution).as_ref().unwrap();
-        let rows: Vec<Vec<usize>> = dlx_solution
+        rows: &[Vec<usize>],
+        dlx_solution: &[usize],
+    ) -> Vec<(usize, Vec<Vec<usize>>)> {
+        let n_pieces = self.polyominoes.len();
+        dlx_solution
             .iter()
-            .map(|&index| matrix[index].clone())
-            .collect();
-        let piece_ids: Vec<usize> = rows
+            .map(|&index| {
+                let (&piece_id, cells) = rows[index].split_first().unwrap();
+                let mut placement = vec![vec![0; self.width]; self.height];
+                for cell in cells.iter().map(|column| column - n_pieces) {
+                    placement[cell / self.width][cell % self.width] = 1;
+                }
+                (piece_id, placement)
+            })
+            .collect()
+    }
+
+    pub fn solve(&self) -> Result<Option<PiecePlacements>, TilingError> {
+        Ok(self.solutions()?.next())
+    }
+
+    /// Searches for a tiling, giving up once one of the limits in `options` is hit.
+    pub fn solve_with_options(
+        &self,
+        options: &SolveOptions,
+    ) -> Result<SolveOutcome<PiecePlacements>, TilingError> {
+        Ok(self.solve_with_stats(options)?.0)
+    }
+
+    /// Like `solve_with_options`, also returning the statistics of the search.
+    pub fn solve_with_stats(
+        &self,
+        options: &SolveOptions,
+    ) -> Result<(SolveOutcome<PiecePlacements>, SearchStats), TilingError> {
+        let Some((rows, mut solver)) = self.build_solver()? else {
+            return Ok((SolveOutcome::Unsolvable, SearchStats::default()));
+        };
+        let outcome = solver
+            .solve_with_options(options)
+            .map(|dlx_solution| self.decode_dlx_solution(&rows, &dlx_solution));
+        Ok((outcome, solver.stats().clone()))
+    }
+
+    /// Like `solve_with_options`, also returning the search tree explored down to
+    /// depth `max_depth`, see `DancingLinks::enable_trace`. The search always runs
+    /// on `DancingLinks`.
+    ///
+    /// Rows are named after the placements, e.g. `F@r1c2/rot90`, and columns after
+    /// the pieces and cells, as in `with_piece_names`.
+    pub fn solve_with_trace(
+        &self,
+        options: &SolveOptions,
+        max_depth: usize,
+    ) -> Result<(SolveOutcome<PiecePlacements>, SearchTrace), TilingError> {
+        let Some((rows, mut dlx)) = self.build_exact_cover_problem()? else {
+            return Ok((SolveOutcome::Unsolvable, SearchTrace::new(max_depth)));
+        };
+        dlx.enable_trace(max_depth);
+        let outcome = dlx
+            .solve_with_options(options)
+            .map(|dlx_solution| self.decode_dlx_solution(&rows, &dlx_solution));
+        Ok((outcome, dlx.take_trace().unwrap_or_default()))
+    }
+
+    /// Lazily enumerates every tiling of the board.
+    ///
+    /// Each item has the same shape as the result of `solve`.
+    pub fn solutions(&self) -> Result<TilingSolutions<'_>, TilingError> {
+        Ok(TilingSolutions {
+            tiling: self,
+            problem: self.build_solver()?,
+        })
+    }
+
+    /// Counts every tiling of the board without materializing any of them.
+    pub fn count_solutions(&self) -> Result<u128, TilingError> {
+        Ok(self.count_solutions_with_stats()?.0)
+    }
+
+    /// Like `count_solutions`, also returning the statistics of the search.
+    pub fn count_solutions_with_stats(&self) -> Result<(u128, SearchStats), TilingError> {
+        Ok(match self.build_solver()? {
+            Some((_, mut solver)) => (solver.count_solutions(), solver.stats().clone()),
+            None => (0, SearchStats::default()),
+        })
+    }
+
+    /// Counts every tiling of the board with the memoized search of
+    /// `DancingLinks::zdd`, which pays off when the board splits into regions that
+    /// many partial tilings leave the same.
+    ///
+    /// Pieces with a quantity other than exactly one are not supported.
+    pub fn count_solutions_memoized(&self) -> Result<u128, TilingError> {
+        Ok(match self.build_exact_cover_problem()? {
+            Some((_, mut dlx)) => dlx.count_solutions_memoized()?,
+            None => 0,
+        })
+    }
+
+    /// Draws `n` tilings of the board uniformly at random from `seed`, out of the
+    /// diagram of all of them built by `DancingLinks::zdd`. Returns no tiling if the
+    /// board has none.
+    ///
+    /// The pieces of each tiling come in the order `DancingLinks` places them, as in
+    /// `solutions` with `Backend::DancingLinks`.
+    ///
+    /// Pieces with a quantity other than exactly one are not supported.
+    pub fn sample_solutions(
+        &self,
+        n: usize,
+        seed: u64,
+    ) -> Result<Vec<PiecePlacements>, TilingError> {
+        let Some((rows, mut dlx)) = self.build_exact_cover_problem()? else {
+            return Ok(Vec::new());
+        };
+        Ok(dlx
+            .zdd()?
+            .sample(n, seed)
             .iter()
-            .map(|r| decode_one_hot(&r[..self.polyominoes.len()]).unwrap())
-            .collect();
-        let placements: Vec<Vec<Vec<usize>>> = rows
+            .map(|dlx_solution| self.decode_dlx_solution(&rows, dlx_solution))
+            .collect())
+    }
+
+    /// Enumerates every tiling of the board, saving the search to the checkpoint
+    /// file at `path` every `interval` and before each tiling so that it can be
+    /// resumed, see `DancingLinks::enumerate_with_checkpoints`.
+    ///
+    /// The search runs on `DancingLinks`, so tilings come in the order `solutions`
+    /// yields them with `Backend::DancingLinks`.
+    ///
+    /// The tiling must be set up the same way when resuming, or an `InvalidData`
+    /// error is returned. An invalid tiling gives an `InvalidInput` error.
+    pub fn enumerate_with_checkpoints(
+        &self,
+        path: impl AsRef<Path>,
+        interval: Duration,
+        options: &SolveOptions,
+        mut on_solution: impl FnMut(PiecePlacements),
+    ) -> io::Result<Option<Limit>> {
+        let problem = self
+            .build_exact_cover_problem()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        let Some((rows, mut dlx)) = problem else {
+            return Ok(None);
+        };
+        dlx.enumerate_with_checkpoints(path, interval, options, |dlx_solution| {
+            on_solution(self.decode_dlx_solution(&rows, dlx_solution))
+        })
+    }
+
+    /// Counts every tiling of the board on several threads.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn count_solutions_parallel(&self, search: &ParallelSearch) -> Result<u128, TilingError> {
+        Ok(match self.build_exact_cover_problem()? {
+            Some((_, dlx)) => search.count_solutions(&dlx),
+            None => 0,
+        })
+    }
+
+    /// Returns every tiling of the board, found on several threads by `DancingLinks`,
+    /// in the order `solutions` yields them with `Backend::DancingLinks`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn solutions_parallel(
+        &self,
+        search: &ParallelSearch,
+    ) -> Result<Vec<PiecePlacements>, TilingError> {
+        Ok(match self.build_exact_cover_problem()? {
+            Some((rows, dlx)) => search
+                .solutions(&dlx)
+                .iter()
+                .map(|dlx_solution| self.decode_dlx_solution(&rows, dlx_solution))
+                .collect(),
+            None => Vec::new(),
+        })
+    }
+
+    /// Writes the exact cover matrix of the tiling in the text format of Knuth's DLX
+    /// programs, see `DancingLinks::write_dlx`, so it can be solved by other solvers.
+    ///
+    /// Items are named after the pieces and cells (`r1c2`), and options are preceded
+    /// by a comment with their label.
+    pub fn write_dlx(&self, writer: impl Write) -> io::Result<()> {
+        let (_, dlx) = self
+            .build_matrix()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        dlx.write_dlx(writer)
+    }
+
+    /// Encodes the tiling as a formula for SAT solvers, see `DancingLinks::to_cnf`,
+    /// with one variable per placement of a piece. `decode_sat_model` turns a model
+    /// back into a tiling.
+    ///
+    /// Pieces with a quantity other than exactly one are not supported.
+    pub fn to_cnf(&self, encoding: AtMostOne) -> Result<Cnf, TilingError> {
+        let (_, dlx) = self.build_matrix()?;
+        Ok(dlx.to_cnf(encoding)?)
+    }
+
+    /// Returns the tiling given by a model of the formula of `to_cnf`, in the same
+    /// shape as the result of `solve`, such as one read by `cnf::parse_model`.
+    pub fn decode_sat_model(&self, model: &[i32]) -> Result<PiecePlacements, TilingError> {
+        let (rows, _) = self.build_matrix()?;
+        let selected: Vec<usize> = Cnf::new(rows.len()).selected_rows(model);
+        Ok(self.decode_dlx_solution(&rows, &selected))
+    }
+
+    /// Builds the exact cover problem of the tiling, or `None` when the pieces can
+    /// not cover the board.
+    fn build_exact_cover_problem(&self) -> Result<Option<ExactCoverProblem>, TilingError> {
+        if !self.can_cover_board()? {
+            return Ok(None);
+        }
+        Ok(Some(self.build_matrix()?))
+    }
+
+    /// Like `build_exact_cover_problem`, with the solver picked by `with_backend`.
+    fn build_solver(&self) -> Result<Option<SolverProblem>, TilingError> {
+        if !self.can_cover_board()? {
+            return Ok(None);
+        }
+        let (rows, _) = self.encode_into_exact_cover_problem_rows();
+        if let Some(bitboard) = self.build_bitboard(&rows) {
+            debug!("solving with the bitboard backend");
+            return Ok(Some((rows, Solver::Bitboard(Box::new(bitboard)))));
+        }
+        let (rows, dlx) = self.build_matrix()?;
+        Ok(Some((rows, Solver::DancingLinks(Box::new(dlx)))))
+    }
+
+    /// Builds the bitboard solver of the exact cover problem with rows `rows` when
+    /// `Backend::Auto` can pick it, or `None` when `DancingLinks` must be used.
+    fn build_bitboard(&self, rows: &[Vec<usize>]) -> Option<Bitboard> {
+        let needs_dlx = self.backend == Backend::DancingLinks
+            || self.progress.is_some()
+            || self.column_chooser.is_some()
+            || self.pruner.is_some()
+            || self.random_seed.is_some();
+        if needs_dlx {
+            return None;
+        }
+        let n_pieces = self.polyominoes.len();
+        let quantities: Vec<(usize, usize)> =
+            (0..n_pieces).map(|id| self.piece_quantity(id)).collect();
+        // The bitboard fills the first free cell first, which works best with the
+        // cells numbered down the columns of a board wider than it is high
+        let (width, height) = (self.width, self.height);
+        let rows: Vec<Vec<usize>> = rows
             .iter()
-            .map(|r| r[self.polyominoes.len()..].to_vec()) // flattened placements
-            .map(|r| {
-                (0..(self.height))
-                    .map(|i| r[(self.width * i)..(self.width * (i + 1))].to_vec())
-                    .collect::<Vec<Vec<usize>>>()
+            .map(|row| {
+                row.iter()
+                    .map(|&column| match column.checked_sub(n_pieces) {
+                        Some(cell) if width > height => {
+                            n_pieces + cell % width * height + cell / width
+                        }
+                        _ => column,
+                    })
+                    .collect()
             })
-            .collect::<Vec<Vec<Vec<usize>>>>();
-
-        let solution: Vec<(usize, Vec<Vec<usize>>)> =
-            piece_ids.into_iter().zip(placements).collect();
-        Some(solution)
+            .collect();
+        Bitboard::new(&quantities, n_pieces + width * height, &rows)
     }
 
-    pub fn solve(&self) -> Option<Vec<(usize, Vec<Vec<usize>>)>> {
+    /// Checks the tiling, returning whether the pieces can cover the board at all.
+    fn can_cover_board(&self) -> Result<bool, TilingError> {
+        self.validate()?;
         if !self.is_board_size_eq_to_number_of_cells_of_polyominoes() {
             info!(
                 "Board size does not match the total number of cells in polyominoes. \
@@ -90,30 +507,448 @@ impl PolyominoTiling {
                     .map(|polyomino| polyomino.iter().flatten().sum::<usize>())
                     .sum::<usize>()
             );
-            return None;
+            return Ok(false);
         }
-        let matrix = self.encode_into_exact_cover_problem_matrix();
+        Ok(true)
+    }
+
+    /// Builds the exact cover matrix of the tiling, whether or not the pieces can
+    /// cover the board, along with the columns covered by each row.
+    fn build_matrix(&self) -> Result<ExactCoverProblem, TilingError> {
+        let (rows, labels) = self.encode_into_exact_cover_problem_rows();
         debug!(
-            "problem reduced into exact cover problem matrix: {:?}",
-            matrix
+            "problem reduced into exact cover problem rows: {:?}",
+            labels
         );
-        let mut dlx =
-            DancingLinks::from_vecs(&matrix, self.width * self.height + self.polyominoes.len());
-        let dlx_solution = dlx.solve();
-        self.decode_dlx_solution(&matrix, &dlx_solution)
+        let mut dlx = DancingLinks::new();
+        let n_pieces = self.polyominoes.len();
+        for piece_id in 0..n_pieces {
+            let (lo, hi) = self.piece_quantity(piece_id);
+            dlx.append_column_with_multiplicity(lo, hi)?;
+            dlx.set_column_label(piece_id, self.piece_name(piece_id))?;
+        }
+        for cell in 0..(self.width * self.height) {
+            dlx.append_column();
+            let (row, column) = (cell / self.width, cell % self.width);
+            dlx.set_column_label(n_pieces + cell, format!("r{}c{}", row, column))?;
+        }
+        for (index, (row, label)) in rows.iter().zip(labels).enumerate() {
+            dlx.append_sparse_row(row)?;
+            dlx.set_row_label(index, label)?;
+        }
+        dlx.set_progress_reporter(self.progress.clone());
+        if let Some(seed) = self.random_seed {
+            dlx.randomize(seed);
+        }
+        if let Some(chooser) = &self.column_chooser {
+            dlx.set_column_chooser(chooser.clone());
+        }
+        if let Some(pruner) = &self.pruner {
+            dlx.set_pruner(pruner.clone());
+        }
+        Ok((rows, dlx))
+    }
+
+    /// Checks the pieces and their quantities, which the encoding relies on.
+    fn validate(&self) -> Result<(), TilingError> {
+        for (piece, polyomino) in self.polyominoes.iter().enumerate() {
+            let width = polyomino.first().map_or(0, Vec::len);
+            if polyomino.iter().any(|row| row.len() != width) {
+                return Err(TilingError::RaggedPiece { piece });
+            }
+            let (min, max) = self.piece_quantity(piece);
+            if max == 0 || min > max {
+                return Err(TilingError::InvalidQuantity { piece, min, max });
+            }
+        }
+        Ok(())
     }
 
+    /// With piece quantities, checks that the board size is within the range of
+    /// total cells the pieces can cover.
     fn is_board_size_eq_to_number_of_cells_of_polyominoes(&self) -> bool {
-        let total_cells: usize = self
+        let (min_cells, max_cells) = self
             .polyominoes
             .iter()
-            .map(|polyomino| polyomino.iter().flatten().sum::<usize>())
+            .enumerate()
+            .map(|(piece_id, polyomino)| {
+                let cells = polyomino.iter().flatten().sum::<usize>();
+                let (lo, hi) = self.piece_quantity(piece_id);
+                (cells * lo, cells * hi)
+            })
+            .fold((0, 0), |(min, max), (lo, hi)| (min + lo, max + hi));
+        debug!(
+            "width={}, height={}, min_cells={}, max_cells={}",
+            self.width, self.height, min_cells, max_cells
+        );
+        (min_cells..=max_cells).contains(&(self.width * self.height))
+    }
+}
+
+/// Iterator over all tilings of a `PolyominoTiling`.
+///
+/// Returned by [`PolyominoTiling::solutions`].
+pub struct TilingSolutions<'a> {
+    tiling: &'a PolyominoTiling,
+    problem: Option<SolverProblem>,
+}
+
+impl Iterator for TilingSolutions<'_> {
+    type Item = Vec<(usize, Vec<Vec<usize>>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (rows, solver) = self.problem.as_mut()?;
+        let dlx_solution = solver.next_solution()?;
+        Some(self.tiling.decode_dlx_solution(rows, &dlx_solution))
+    }
+}
+
+/// Solver of the exact cover problem of a tiling, see `Backend`.
+///
+/// Both number rows the same way, so their solutions decode alike.
+enum Solver {
+    DancingLinks(Box<DancingLinks>),
+    Bitboard(Box<Bitboard>),
+}
+
+impl Solver {
+    fn next_solution(&mut self) -> Option<Vec<usize>> {
+        match self {
+            Solver::DancingLinks(dlx) => dlx.next_solution(),
+            Solver::Bitboard(bitboard) => bitboard.next_solution(),
+        }
+    }
+
+    fn solve_with_options(&mut self, options: &SolveOptions) -> SolveOutcome<Vec<usize>> {
+        match self {
+            Solver::DancingLinks(dlx) => dlx.solve_with_options(options),
+            Solver::Bitboard(bitboard) => bitboard.solve_with_options(options),
+        }
+    }
+
+    fn count_solutions(&mut self) -> u128 {
+        match self {
+            Solver::DancingLinks(dlx) => dlx.count_solutions(),
+            Solver::Bitboard(bitboard) => bitboard.count_solutions(),
+        }
+    }
+
+    fn stats(&self) -> &SearchStats {
+        match self {
+            Solver::DancingLinks(dlx) => dlx.stats(),
+            Solver::Bitboard(bitboard) => bitboard.stats(),
+        }
+    }
+}
+
+/// Prunes partial tilings that leave a region of empty cells, connected by their
+/// sides, smaller than the smallest piece left or whose size is not a multiple of
+/// the greatest common divisor of the sizes of the pieces left.
+///
+/// A piece is left while its column is active. Columns are laid out as in
+/// `PolyominoTiling`, and pieces must be connected, so that each fits in a region.
+#[derive(Clone, Debug)]
+struct RegionPruner {
+    width: usize,
+    height: usize,
+    /// Number of cells of each piece
+    piece_sizes: Vec<usize>,
+    /// Empty cells not yet assigned to a region, and cells to visit in the current
+    /// one, kept between calls to save allocations
+    unvisited: Vec<bool>,
+    to_visit: Vec<usize>,
+}
+
+impl RegionPruner {
+    fn new(width: usize, height: usize, piece_sizes: Vec<usize>) -> Self {
+        Self {
+            width,
+            height,
+            piece_sizes,
+            unvisited: vec![false; width * height],
+            to_visit: Vec::new(),
+        }
+    }
+
+    /// Returns the number of cells of the region of `start`, marking them visited.
+    fn visit_region(&mut self, start: usize) -> usize {
+        self.unvisited[start] = false;
+        self.to_visit.push(start);
+        let mut size = 0;
+        while let Some(cell) = self.to_visit.pop() {
+            size += 1;
+            let (row, column) = (cell / self.width, cell % self.width);
+            let neighbors = [
+                (column > 0).then(|| cell - 1),
+                (column + 1 < self.width).then(|| cell + 1),
+                (row > 0).then(|| cell - self.width),
+                (row + 1 < self.height).then(|| cell + self.width),
+            ];
+            for neighbor in neighbors.into_iter().flatten() {
+                if self.unvisited[neighbor] {
+                    self.unvisited[neighbor] = false;
+                    self.to_visit.push(neighbor);
+                }
+            }
+        }
+        size
+    }
+}
+
+impl Pruner for RegionPruner {
+    fn keep(&mut self, _row: usize, columns: ActiveColumns<'_>) -> bool {
+        let n_pieces = self.piece_sizes.len();
+        let (mut min_size, mut gcd) = (usize::MAX, 0);
+        self.unvisited.fill(false);
+        for column in columns {
+            match self.piece_sizes.get(column.index) {
+                Some(&size) => {
+                    min_size = min_size.min(size);
+                    gcd = greatest_common_divisor(gcd, size);
+                }
+                None => self.unvisited[column.index - n_pieces] = true,
+            }
+        }
+        for start in 0..self.unvisited.len() {
+            if !self.unvisited[start] {
+                continue;
+            }
+            let size = self.visit_region(start);
+            if size < min_size || !size.is_multiple_of(gcd) {
+                debug!("pruning a region of {} empty cells", size);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn greatest_common_divisor(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        greatest_common_divisor(b, a % b)
+    }
+}
+
+/// Whether the cells of the piece are connected by their sides. A piece with no
+/// cell is, one whose rows do not all have the same length is not.
+fn is_connected(polyomino: &[Vec<usize>]) -> bool {
+    let width = polyomino.first().map_or(0, Vec::len);
+    if polyomino.iter().any(|row| row.len() != width) {
+        return false;
+    }
+    let cells: Vec<usize> = flatten(polyomino)
+        .iter()
+        .enumerate()
+        .filter(|&(_, &value)| value != 0)
+        .map(|(cell, _)| cell)
+        .collect();
+    let Some(&first) = cells.first() else {
+        return true;
+    };
+    let mut pruner = RegionPruner::new(width, polyomino.len(), Vec::new());
+    for &cell in &cells {
+        pruner.unvisited[cell] = true;
+    }
+    pruner.visit_region(first) == cells.len()
+}
+
+/// A tiling whose pieces are added and removed one at a time, keeping its exact
+/// cover matrix between searches.
+///
+/// `PolyominoTiling` encodes every placement again for each search. Here, adding a
+/// piece only appends its column and placements to the matrix, and removing it only
+/// unlinks them, see `DancingLinks::remove_row`. The matrix is built again once
+/// removed placements outnumber the others.
+///
+/// Each piece is used exactly once. Pieces are numbered by their position among the
+/// pieces left, which is the piece id of the tilings found, so they match those of
+/// a `PolyominoTiling` of the same pieces.
+pub struct IncrementalTiling {
+    width: usize,
+    height: usize,
+    pieces: Vec<IncrementalPiece>,
+    dlx: DancingLinks,
+    /// Columns of the matrix, including removed ones
+    n_cols: usize,
+    /// Cells covered by each row of the matrix, including removed ones
+    row_cells: Vec<Vec<usize>>,
+    n_removed_rows: usize,
+    random_seed: Option<u64>,
+}
+
+/// A piece of an `IncrementalTiling` and where it is in the matrix.
+struct IncrementalPiece {
+    polyomino: Vec<Vec<usize>>,
+    column: usize,
+    rows: Range<usize>,
+}
+
+impl IncrementalTiling {
+    /// Starts a tiling of the board with no piece.
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut tiling = IncrementalTiling {
+            width,
+            height,
+            pieces: Vec::new(),
+            dlx: DancingLinks::new(),
+            n_cols: 0,
+            row_cells: Vec::new(),
+            n_removed_rows: 0,
+            random_seed: None,
+        };
+        tiling.clear_matrix();
+        tiling
+    }
+
+    /// Searches in a random order drawn from `seed`, or in the default order when
+    /// `None`, see `PolyominoTiling::with_random_seed`.
+    pub fn set_random_seed(&mut self, seed: Option<u64>) {
+        self.random_seed = seed;
+    }
+
+    pub fn n_pieces(&self) -> usize {
+        self.pieces.len()
+    }
+
+    /// Adds a piece after the others, appending its placements to the matrix.
+    pub fn add_piece(&mut self, polyomino: Vec<Vec<usize>>) -> Result<(), TilingError> {
+        let width = polyomino.first().map_or(0, Vec::len);
+        if polyomino.iter().any(|row| row.len() != width) {
+            return Err(TilingError::RaggedPiece {
+                piece: self.pieces.len(),
+            });
+        }
+        self.append_piece(polyomino)?;
+        Ok(())
+    }
+
+    /// Removes piece `piece`, returning it. The pieces after it move down by one.
+    pub fn remove_piece(&mut self, piece: usize) -> Result<Vec<Vec<usize>>, TilingError> {
+        if piece >= self.pieces.len() {
+            return Err(TilingError::PieceOutOfRange {
+                piece,
+                n_pieces: self.pieces.len(),
+            });
+        }
+        // The piece is only dropped once the matrix no longer holds it
+        let IncrementalPiece { rows, column, .. } = &self.pieces[piece];
+        for row in rows.clone() {
+            self.dlx.remove_row(row)?;
+        }
+        self.dlx.remove_column(*column)?;
+        let removed = self.pieces.remove(piece);
+        self.n_removed_rows += removed.rows.len();
+        if self.n_removed_rows > self.row_cells.len() - self.n_removed_rows {
+            self.rebuild()?;
+        }
+        Ok(removed.polyomino)
+    }
+
+    /// Finds a tiling of the board with the pieces added so far, or `None` when there
+    /// is none.
+    pub fn solve(&mut self) -> Option<PiecePlacements> {
+        let dlx_solution = self.search(DancingLinks::solve).flatten()?;
+        Some(self.decode_dlx_solution(&dlx_solution))
+    }
+
+    /// Searches for a tiling, giving up once one of the limits in `options` is hit.
+    pub fn solve_with_options(&mut self, options: &SolveOptions) -> SolveOutcome<PiecePlacements> {
+        match self.search(|dlx| dlx.solve_with_options(options)) {
+            Some(outcome) => outcome.map(|dlx_solution| self.decode_dlx_solution(&dlx_solution)),
+            None => SolveOutcome::Unsolvable,
+        }
+    }
+
+    /// Counts every tiling of the board without materializing any of them.
+    pub fn count_solutions(&mut self) -> u128 {
+        self.search(DancingLinks::count_solutions).unwrap_or(0)
+    }
+
+    /// Runs `search` on the matrix, or on a shuffled copy of it when a random seed is
+    /// set, or returns `None` when the pieces do not have as many cells as the board.
+    fn search<T>(&mut self, search: impl FnOnce(&mut DancingLinks) -> T) -> Option<T> {
+        let n_cells: usize = self
+            .pieces
+            .iter()
+            .map(|piece| piece.polyomino.iter().flatten().sum::<usize>())
             .sum();
+        if n_cells != self.width * self.height {
+            info!(
+                "Board size does not match the total number of cells in polyominoes. \
+                width={}, height={}, total_cells={}",
+                self.width, self.height, n_cells
+            );
+            return None;
+        }
+        Some(match self.random_seed {
+            Some(seed) => {
+                let mut dlx = self.dlx.clone();
+                dlx.randomize(seed);
+                search(&mut dlx)
+            }
+            None => search(&mut self.dlx),
+        })
+    }
+
+    fn decode_dlx_solution(&self, dlx_solution: &[usize]) -> PiecePlacements {
+        dlx_solution
+            .iter()
+            .map(|&row| {
+                let piece_id = self.pieces.partition_point(|piece| piece.rows.end <= row);
+                let mut placement = vec![vec![0; self.width]; self.height];
+                for &cell in &self.row_cells[row] {
+                    placement[cell / self.width][cell % self.width] = 1;
+                }
+                (piece_id, placement)
+            })
+            .collect()
+    }
+
+    /// Appends a column for the piece and a row for each of its placements, covering
+    /// its cells and then that column.
+    fn append_piece(&mut self, polyomino: Vec<Vec<usize>>) -> Result<(), DlxError> {
+        let column = self.n_cols;
+        self.dlx.append_column();
+        self.n_cols += 1;
+        let first = self.row_cells.len();
+        for (_, cells) in get_all_placements(&polyomino, self.width, self.height) {
+            let row: Vec<usize> = cells.iter().copied().chain([column]).collect();
+            self.dlx.append_sparse_row(&row)?;
+            self.row_cells.push(cells);
+        }
+        self.pieces.push(IncrementalPiece {
+            polyomino,
+            column,
+            rows: first..self.row_cells.len(),
+        });
+        Ok(())
+    }
+
+    /// Starts a matrix with a column per cell of the board and nothing else.
+    fn clear_matrix(&mut self) {
+        self.dlx = DancingLinks::new();
+        self.n_cols = self.width * self.height;
+        (0..self.n_cols).for_each(|_| self.dlx.append_column());
+        self.row_cells.clear();
+        self.n_removed_rows = 0;
+    }
+
+    /// Builds the matrix again from the pieces left, dropping removed rows and columns.
+    fn rebuild(&mut self) -> Result<(), DlxError> {
         debug!(
-            "width={}, height={}, total_cells={}",
-            self.width, self.height, total_cells
+            "rebuilding the matrix of {} pieces, {} of {} rows were removed",
+            self.pieces.len(),
+            self.n_removed_rows,
+            self.row_cells.len()
         );
-        total_cells == self.width * self.height
+        let pieces = std::mem::take(&mut self.pieces);
+        self.clear_matrix();
+        for piece in pieces {
+            self.append_piece(piece.polyomino)?;
+        }
+        Ok(())
     }
 }
 
@@ -161,13 +996,15 @@ fn get_all_rotations_and_mirrors(matrix: &[Vec<usize>]) -> Vec<Vec<Vec<usize>>>
     all
 }
 
+/// Lists the board cells covered by each placement of the piece, in increasing
+/// order, the cells of the board being numbered row by row.
 fn get_all_placements_without_rotation_nor_mirror(
     matrix: &[Vec<usize>],
     width: usize,
     height: usize,
-) -> Vec<Vec<Vec<usize>>> {
+) -> Vec<Vec<usize>> {
     if matrix.is_empty() || matrix[0].is_empty() {
-        return vec![vec![vec![0; width]; height]];
+        return vec![vec![]];
     }
     let piece_height = matrix.len();
     let piece_width = matrix[0].len();
@@ -181,27 +1018,63 @@ fn get_all_placements_without_rotation_nor_mirror(
         );
         return placements;
     }
+    // Cells of the piece relative to the cell of its top-left corner
+    let offsets: Vec<usize> = flatten(matrix)
+        .iter()
+        .enumerate()
+        .filter(|&(_, &value)| value != 0)
+        .map(|(k, _)| (k / piece_width) * width + k % piece_width)
+        .collect();
     for i in 0..=height - piece_height {
         for j in 0..=width - piece_width {
-            let mut placement = vec![vec![0; width]; height];
-            for r in 0..piece_height {
-                for c in 0..piece_width {
-                    placement[i + r][j + c] = matrix[r][c];
-                }
-            }
-            placements.push(placement);
+            let corner = i * width + j;
+            placements.push(offsets.iter().map(|offset| corner + offset).collect());
         }
     }
     placements
 }
 
-fn get_all_placements(matrix: &[Vec<usize>], width: usize, height: usize) -> Vec<Vec<Vec<usize>>> {
+/// Names an orientation of `get_all_rotations_and_mirrors`, e.g. `mirror/rot90` for
+/// the mirror image rotated clockwise by 90 degrees.
+fn orientation_name(orientation: usize) -> String {
+    let rotation = 90 * (orientation % 4);
+    if orientation < 4 {
+        format!("rot{}", rotation)
+    } else {
+        format!("mirror/rot{}", rotation)
+    }
+}
+
+/// Lists every placement of the piece as the index of its orientation in
+/// `get_all_rotations_and_mirrors` and the board cells it covers.
+fn get_all_placements(
+    matrix: &[Vec<usize>],
+    width: usize,
+    height: usize,
+) -> Vec<(usize, Vec<usize>)> {
     let mut placements = vec![];
-    let all_rotations_and_mirrors = get_all_rotations_and_mirrors(matrix);
-    for m in all_rotations_and_mirrors {
-        placements.extend(get_all_placements_without_rotation_nor_mirror(
-            &m, width, height,
-        ));
+    let mut all_rotations_and_mirrors: Vec<(usize, Vec<Vec<usize>>)> =
+        get_all_rotations_and_mirrors(matrix)
+            .into_iter()
+            .enumerate()
+            .collect();
+    // Symmetric pieces yield the same orientation more than once. Keep only the
+    // first of each so that every tiling is enumerated exactly once.
+    let mut seen: Vec<Vec<Vec<usize>>> = vec![];
+    all_rotations_and_mirrors.retain(|(_, m)| {
+        if seen.contains(m) {
+            false
+        } else {
+            seen.push(m.clone());
+            true
+        }
+    });
+    for (orientation, m) in all_rotations_and_mirrors {
+        placements.extend(
+            get_all_placements_without_rotation_nor_mirror(&m, width, height)
+                .into_iter()
+                .map(|cells| (orientation, cells)),
+        );
     }
     debug!(
         "Found {} placements for piece with dimensions {}x{}",
@@ -220,24 +1093,6 @@ fn flatten(matrix: &[Vec<usize>]) -> Vec<usize> {
     matrix.iter().flat_map(|row| row.iter()).cloned().collect()
 }
 
-fn encode_one_hot(len: usize, index: usize) -> Vec<usize> {
-    let mut vec = vec![0; len];
-    if index < len {
-        vec[index] = 1;
-    } else {
-        panic!("Index out of bounds for one-hot encoding");
-    }
-    vec
-}
-
-fn decode_one_hot(encoded: &[usize]) -> Option<usize> {
-    encoded
-        .iter()
-        .enumerate()
-        .find(|&(_, &val)| val == 1)
-        .map(|(index, _)| index)
-}
-
 pub fn piece_placements_to_matrix_of_piece_ids(
     piece_placements: &[(usize, Vec<Vec<usize>>)],
     width: usize,
@@ -277,6 +1132,29 @@ pub fn piece_placements_to_matrix_of_piece_ids(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::DlxError;
+    use crate::pretty::str_to_matrix;
+    use crate::solve_options::CancellationToken;
+    use crate::trace::TraceEvent;
+    use std::sync::{Arc, Mutex};
+
+    /// The 12 pentominoes, which fill 60 cells
+    fn pentominoes() -> Vec<Vec<Vec<usize>>> {
+        vec![
+            str_to_matrix(vec!["###", "#..", "#.."]),
+            str_to_matrix(vec!["####", "#..."]),
+            str_to_matrix(vec!["#####"]),
+            str_to_matrix(vec!["##", "#.", "##"]),
+            str_to_matrix(vec![".##", ".#.", "##."]),
+            str_to_matrix(vec![".###", "##.."]),
+            str_to_matrix(vec![".#.", "###", ".#."]),
+            str_to_matrix(vec!["##.", ".##", ".#."]),
+            str_to_matrix(vec!["###", ".#.", ".#."]),
+            str_to_matrix(vec!["####", ".#.."]),
+            str_to_matrix(vec![".##", "##.", "#.."]),
+            str_to_matrix(vec!["#.", "##", "##"]),
+        ]
+    }
 
     #[test]
     fn test_flatten() {
@@ -377,27 +1255,12 @@ mod tests {
             vec![0, 1, 1], //
         ];
         let mut actual = get_all_placements_without_rotation_nor_mirror(&matrix, 4, 3);
-        let mut expected: Vec<Vec<Vec<usize>>> = vec![
-            vec![
-                vec![1, 0, 0, 0], //
-                vec![0, 1, 1, 0], //
-                vec![0, 0, 0, 0], //
-            ],
-            vec![
-                vec![0, 1, 0, 0], //
-                vec![0, 0, 1, 1], //
-                vec![0, 0, 0, 0], //
-            ],
-            vec![
-                vec![0, 0, 0, 0], //
-                vec![1, 0, 0, 0], //
-                vec![0, 1, 1, 0], //
-            ],
-            vec![
-                vec![0, 0, 0, 0], //
-                vec![0, 1, 0, 0], //
-                vec![0, 0, 1, 1], //
-            ],
+        // Cells are numbered row by row on the 4x3 board
+        let mut expected: Vec<Vec<usize>> = vec![
+            vec![0, 5, 6],
+            vec![1, 6, 7],
+            vec![4, 9, 10],
+            vec![5, 10, 11],
         ];
         actual.sort();
         expected.sort();
@@ -449,10 +1312,593 @@ mod tests {
             vec![0, 1, 0], //
         ]];
         let tiling = PolyominoTiling::new(1, 5, polyominos);
-        let solution = tiling.solve();
+        let solution = tiling.solve().unwrap();
         assert!(
             solution.is_none(),
             "Expected no solution for mismatched board size"
         );
     }
+
+    #[test]
+    fn test_labels() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let pieces = vec![str_to_matrix(vec!["##", "#."]), vec![vec![1]]];
+        let tiling = PolyominoTiling::new(2, 2, pieces).with_piece_names(vec!["V".to_string()]);
+        let (_, mut dlx) = tiling.build_exact_cover_problem().unwrap().unwrap();
+        assert_eq!(dlx.column_label(0), Some("V"));
+        assert_eq!(dlx.column_label(1), Some("1"));
+        assert_eq!(dlx.column_label(2), Some("r0c0"));
+        assert_eq!(dlx.column_label(5), Some("r1c1"));
+        // The V piece fits in 4 orientations, the monomino in 4 cells
+        let labels: Vec<&str> = (0..8).map(|row| dlx.row_label(row).unwrap()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "V@r0c0/rot0",
+                "V@r0c0/rot90",
+                "V@r0c0/rot180",
+                "V@r0c0/rot270",
+                "1@r0c0/rot0",
+                "1@r0c1/rot0",
+                "1@r1c0/rot0",
+                "1@r1c1/rot0",
+            ]
+        );
+
+        let solutions: Vec<Vec<usize>> = dlx.solutions().collect();
+        let mut solutions: Vec<Vec<String>> = solutions
+            .iter()
+            .map(|solution| dlx.solution_labels(solution))
+            .collect();
+        solutions.sort();
+        assert_eq!(solutions.len(), 4);
+        assert_eq!(solutions[0], vec!["V@r0c0/rot0", "1@r1c1/rot0"]);
+    }
+
+    #[test]
+    fn test_write_dlx() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let pieces = vec![str_to_matrix(vec!["##", "#."]), vec![vec![1]]];
+        let tiling = PolyominoTiling::new(2, 2, pieces).with_piece_names(vec!["V".to_string()]);
+        let mut text = Vec::new();
+        tiling.write_dlx(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], "V 1 r0c0 r0c1 r1c0 r1c1");
+        assert_eq!(lines[2..4], ["| V@r0c0/rot0", "V r0c0 r0c1 r1c0"]);
+        assert_eq!(lines.len(), 2 + 2 * 8);
+
+        let mut dlx = DancingLinks::read_dlx(text.as_bytes()).unwrap();
+        assert_eq!(dlx.count_solutions(), tiling.count_solutions().unwrap());
+        assert_eq!(dlx.column_label(2), Some("r0c0"));
+
+        // The matrix is written even when the pieces can not cover the board
+        let tiling = PolyominoTiling::new(3, 3, vec![vec![vec![1, 1]]]);
+        let mut text = Vec::new();
+        tiling.write_dlx(&mut text).unwrap();
+        let mut dlx = DancingLinks::read_dlx(text.as_slice()).unwrap();
+        assert_eq!(dlx.to_vecs().len(), 12);
+        assert_eq!(dlx.count_solutions(), 0);
+    }
+
+    #[test]
+    fn test_cnf() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let pieces = vec![str_to_matrix(vec!["##", "#."]), vec![vec![1]]];
+        let tiling = PolyominoTiling::new(2, 2, pieces).with_piece_names(vec!["V".to_string()]);
+        let cnf = tiling.to_cnf(AtMostOne::Sequential).unwrap();
+        // 8 placements, and a counter for each column with several of them
+        assert_eq!(cnf.n_vars(), 8 + 3 + 3 + 4 * 3);
+        let mut text = Vec::new();
+        cnf.write_dimacs(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("c 1 V@r0c0/rot0\n"));
+        assert!(text.contains(&format!("p cnf 26 {}\n", cnf.clauses().len())));
+
+        // The V piece in its first orientation and the monomino in the last cell
+        let model = [1, -2, -3, -4, -5, -6, -7, 8, 9, -10];
+        let mut solution = tiling.decode_sat_model(&model).unwrap();
+        let mut expected = tiling
+            .solutions()
+            .unwrap()
+            .find(|solution| solution[0].1 == vec![vec![1, 1], vec![1, 0]])
+            .unwrap();
+        solution.sort();
+        expected.sort();
+        assert_eq!(solution, expected);
+
+        let tiling =
+            PolyominoTiling::new(3, 2, vec![vec![vec![1, 1]]]).with_piece_quantities(vec![(3, 3)]);
+        assert_eq!(
+            tiling.to_cnf(AtMostOne::Pairwise).err(),
+            Some(TilingError::Matrix(DlxError::CnfUnsupported))
+        );
+    }
+
+    #[test]
+    fn test_invalid_tiling() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let ragged = vec![vec![1, 1], vec![1]];
+        let tiling = PolyominoTiling::new(3, 1, vec![vec![vec![1, 1]], ragged]);
+        assert_eq!(
+            tiling.solve().err(),
+            Some(TilingError::RaggedPiece { piece: 1 })
+        );
+
+        let tiling =
+            PolyominoTiling::new(2, 1, vec![vec![vec![1]]]).with_piece_quantities(vec![(3, 2)]);
+        assert_eq!(
+            tiling.count_solutions().err(),
+            Some(TilingError::InvalidQuantity {
+                piece: 0,
+                min: 3,
+                max: 2
+            })
+        );
+        assert!(tiling.solutions().is_err());
+    }
+
+    #[test]
+    fn test_solutions_enumerates_every_tiling_once() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]], vec![vec![1, 1]]];
+        let tiling = PolyominoTiling::new(2, 2, dominoes);
+        let mut solutions: Vec<Vec<Vec<Option<usize>>>> = tiling
+            .solutions()
+            .unwrap()
+            .map(|solution| piece_placements_to_matrix_of_piece_ids(&solution, 2, 2))
+            .collect();
+        solutions.sort();
+        assert_eq!(
+            solutions,
+            vec![
+                vec![vec![Some(0), Some(0)], vec![Some(1), Some(1)]],
+                vec![vec![Some(0), Some(1)], vec![Some(0), Some(1)]],
+                vec![vec![Some(1), Some(0)], vec![Some(1), Some(0)]],
+                vec![vec![Some(1), Some(1)], vec![Some(0), Some(0)]],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_solutions() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]], vec![vec![1, 1]]];
+        let tiling = PolyominoTiling::new(2, 2, dominoes);
+        assert_eq!(tiling.count_solutions().unwrap(), 4);
+
+        // The 3x20 pentomino rectangle has 2 distinct tilings, each in 4 orientations
+        let tiling = PolyominoTiling::new(20, 3, pentominoes());
+        assert_eq!(tiling.count_solutions().unwrap(), 8);
+
+        let tiling = PolyominoTiling::new(3, 3, vec![vec![vec![1, 1]]]);
+        assert_eq!(tiling.count_solutions().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_backends() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let domino = vec![vec![1, 1]];
+        let puzzles = vec![
+            (2, 2, vec![domino.clone(); 2], None),
+            (4, 2, vec![domino.clone(); 4], None),
+            (20, 3, pentominoes(), None),
+            (3, 20, pentominoes(), None),
+            (3, 3, vec![domino.clone()], None),
+            (3, 2, vec![domino], Some(vec![(3, 3)])),
+            (
+                4,
+                4,
+                vec![str_to_matrix(vec!["##", "#."]), vec![vec![1]]],
+                Some(vec![(4, 5), (1, 4)]),
+            ),
+        ];
+        for (width, height, pieces, quantities) in puzzles {
+            let tiling = |backend| {
+                let tiling =
+                    PolyominoTiling::new(width, height, pieces.clone()).with_backend(backend);
+                match &quantities {
+                    Some(quantities) => tiling.with_piece_quantities(quantities.clone()),
+                    None => tiling,
+                }
+            };
+            let to_board = |solution: &PiecePlacements| {
+                piece_placements_to_matrix_of_piece_ids(solution, width, height)
+            };
+            let dlx = tiling(Backend::DancingLinks);
+            let mut expected: Vec<_> = dlx.solutions().unwrap().map(|s| to_board(&s)).collect();
+            expected.sort();
+            assert_eq!(dlx.count_solutions().unwrap(), expected.len() as u128);
+
+            // The bitboard finds the same tilings as DancingLinks, in another order
+            let auto = tiling(Backend::Auto);
+            let (rows, _) = auto.encode_into_exact_cover_problem_rows();
+            assert!(auto.build_bitboard(&rows).is_some());
+            assert!(dlx.build_bitboard(&rows).is_none());
+            let mut boards: Vec<_> = auto.solutions().unwrap().map(|s| to_board(&s)).collect();
+            boards.sort();
+            assert_eq!(boards, expected, "{}x{}", width, height);
+            match auto.solve().unwrap() {
+                Some(solution) => assert!(expected.contains(&to_board(&solution))),
+                None => assert!(expected.is_empty()),
+            }
+            match auto.solve_with_options(&SolveOptions::default()).unwrap() {
+                SolveOutcome::Solved(solution) => assert!(expected.contains(&to_board(&solution))),
+                outcome => assert!(expected.is_empty(), "{:?}", outcome),
+            }
+            assert_eq!(auto.count_solutions().unwrap(), expected.len() as u128);
+        }
+    }
+
+    #[test]
+    fn test_solve_with_options() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]], vec![vec![1, 1]]];
+        let tiling = PolyominoTiling::new(2, 2, dominoes);
+        assert_eq!(
+            tiling.solve_with_options(&SolveOptions::default()).unwrap(),
+            SolveOutcome::Solved(tiling.solve().unwrap().unwrap())
+        );
+        let options = SolveOptions {
+            max_nodes: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            tiling.solve_with_options(&options).unwrap(),
+            SolveOutcome::LimitReached {
+                limit: Limit::Nodes,
+                nodes: 1
+            }
+        );
+
+        let tiling = PolyominoTiling::new(3, 3, vec![vec![vec![1, 1]]]);
+        assert_eq!(
+            tiling.solve_with_options(&options).unwrap(),
+            SolveOutcome::Unsolvable
+        );
+    }
+
+    #[test]
+    fn test_count_solutions_with_stats() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]], vec![vec![1, 1]]];
+        let tiling = PolyominoTiling::new(2, 2, dominoes);
+        let (count, stats) = tiling.count_solutions_with_stats().unwrap();
+        assert_eq!(count, 4);
+        assert_eq!(stats.solutions, 4);
+        // Both pieces get placed, one per level
+        assert_eq!(stats.max_depth(), 2);
+
+        let (outcome, stats) = tiling.solve_with_stats(&SolveOptions::default()).unwrap();
+        assert!(outcome.solution().is_some());
+        assert_eq!(stats.solutions, 1);
+
+        let tiling = PolyominoTiling::new(3, 3, vec![vec![vec![1, 1]]]);
+        assert_eq!(
+            tiling.count_solutions_with_stats().unwrap(),
+            (0, SearchStats::default())
+        );
+    }
+
+    #[test]
+    fn test_region_pruning() {
+        _ = env_logger::builder().is_test(true).try_init();
+        // The 3x20 pentomino rectangle, which has 8 tilings. Pruning runs on
+        // DancingLinks, so the search it is compared with must too
+        let tiling = PolyominoTiling::new(20, 3, pentominoes()).with_backend(Backend::DancingLinks);
+        let (count, stats) = tiling.count_solutions_with_stats().unwrap();
+        let pruning = PolyominoTiling::new(20, 3, pentominoes()).with_region_pruning();
+        let (pruned_count, pruned_stats) = pruning.count_solutions_with_stats().unwrap();
+        assert_eq!((pruned_count, count), (8, 8));
+        assert!(pruned_stats.pruned > 0);
+        assert!(pruned_stats.nodes < stats.nodes);
+
+        // Pieces of different sizes, used several times
+        let pieces = vec![
+            str_to_matrix(vec!["##"]),
+            str_to_matrix(vec!["###"]),
+            str_to_matrix(vec!["##", "#."]),
+        ];
+        for (width, height) in [(3, 4), (5, 2), (4, 4)] {
+            let tiling = |pruning: bool| {
+                let tiling = PolyominoTiling::new(width, height, pieces.clone())
+                    .with_piece_quantities(vec![(0, 3), (1, 2), (0, 2)]);
+                if pruning {
+                    tiling.with_region_pruning()
+                } else {
+                    tiling
+                }
+            };
+            assert_eq!(
+                tiling(true).count_solutions().unwrap(),
+                tiling(false).count_solutions().unwrap(),
+                "{}x{}",
+                width,
+                height
+            );
+        }
+
+        assert!(is_connected(&str_to_matrix(vec![".#", "##"])));
+        assert!(is_connected(&[]));
+        assert!(!is_connected(&str_to_matrix(vec!["#.#"])));
+        assert!(!is_connected(&[vec![1, 1], vec![1]]));
+        // A piece that is not connected turns pruning off
+        let split = vec![str_to_matrix(vec!["#.#"]), str_to_matrix(vec!["#"])];
+        let tiling = PolyominoTiling::new(3, 1, split).with_region_pruning();
+        assert!(tiling.pruner.is_none());
+        assert_eq!(tiling.count_solutions().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_column_preference() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]]; 4];
+        // 5 tilings of a 2x4 board by dominoes, times 4! ways to number them
+        for preference in [ColumnPreference::Pieces, ColumnPreference::Cells] {
+            let tiling =
+                PolyominoTiling::new(4, 2, dominoes.clone()).with_column_preference(preference);
+            assert_eq!(tiling.count_solutions().unwrap(), 120);
+        }
+
+        let tiling = PolyominoTiling::new(4, 2, dominoes)
+            .with_column_chooser(Box::new(crate::column_chooser::FirstColumn));
+        assert_eq!(tiling.count_solutions().unwrap(), 120);
+    }
+
+    #[test]
+    fn test_random_seed() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]]; 4];
+        let tiling = |seed| PolyominoTiling::new(4, 2, dominoes.clone()).with_random_seed(seed);
+        let mut distinct = Vec::new();
+        for seed in 0..10 {
+            let solution = tiling(seed).solve().unwrap().unwrap();
+            assert_eq!(solution.len(), 4);
+            assert_eq!(tiling(seed).solve().unwrap().unwrap(), solution);
+            assert_eq!(tiling(seed).count_solutions().unwrap(), 120);
+            let board = piece_placements_to_matrix_of_piece_ids(&solution, 4, 2);
+            assert!(board.iter().flatten().all(|cell| cell.is_some()));
+            distinct.push(board);
+        }
+        distinct.sort();
+        distinct.dedup();
+        assert!(distinct.len() > 1);
+    }
+
+    #[test]
+    fn test_solve_with_trace() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]], vec![vec![1, 1]]];
+        let tiling = PolyominoTiling::new(2, 2, dominoes).with_piece_names(vec!["A".into()]);
+        let options = SolveOptions::default();
+        let (outcome, trace) = tiling.solve_with_trace(&options, 10).unwrap();
+        assert!(matches!(outcome, SolveOutcome::Solved(_)));
+        let last = trace.events().last().unwrap();
+        assert!(matches!(last, TraceEvent::Solution { .. }), "{:?}", last);
+
+        let mut dot = Vec::new();
+        trace.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("A@r0c0/rot0"), "{}", dot);
+
+        let (outcome, trace) = PolyominoTiling::new(3, 3, vec![vec![vec![1]]])
+            .solve_with_trace(&options, 10)
+            .unwrap();
+        assert!(matches!(outcome, SolveOutcome::Unsolvable));
+        assert!(trace.events().is_empty());
+    }
+
+    #[test]
+    fn test_incremental_tiling() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let l = str_to_matrix(vec!["###", "#.."]);
+        let o = str_to_matrix(vec!["##", "##"]);
+        let i = str_to_matrix(vec!["####"]);
+        let s = str_to_matrix(vec![".##", "##."]);
+        let domino = str_to_matrix(vec!["##"]);
+
+        let mut tiling = IncrementalTiling::new(4, 4);
+        let mut pieces: Vec<Vec<Vec<usize>>> = Vec::new();
+        let check = |tiling: &mut IncrementalTiling, pieces: &[Vec<Vec<usize>>]| {
+            let fresh = PolyominoTiling::new(4, 4, pieces.to_vec());
+            let boards: Vec<_> = fresh
+                .solutions()
+                .unwrap()
+                .map(|solution| piece_placements_to_matrix_of_piece_ids(&solution, 4, 4))
+                .collect();
+            assert_eq!(tiling.n_pieces(), pieces.len());
+            assert_eq!(tiling.count_solutions(), boards.len() as u128);
+            let solution = tiling.solve();
+            assert_eq!(solution.is_some(), !boards.is_empty());
+            if let Some(solution) = solution {
+                let board = piece_placements_to_matrix_of_piece_ids(&solution, 4, 4);
+                assert!(boards.contains(&board), "{:?}", board);
+            }
+        };
+
+        for piece in [&l, &l, &o, &i, &domino, &domino] {
+            tiling.add_piece(piece.clone()).unwrap();
+            pieces.push(piece.clone());
+            check(&mut tiling, &pieces);
+        }
+        // Removing pieces renumbers the ones after them
+        assert_eq!(tiling.remove_piece(2).unwrap(), o);
+        pieces.remove(2);
+        check(&mut tiling, &pieces);
+        tiling.add_piece(s.clone()).unwrap();
+        pieces.push(s.clone());
+        check(&mut tiling, &pieces);
+        // Removing most rows builds the matrix again
+        while !pieces.is_empty() {
+            assert_eq!(tiling.remove_piece(0).unwrap(), pieces.remove(0));
+            check(&mut tiling, &pieces);
+        }
+        assert!(tiling.row_cells.is_empty());
+        for piece in [&l, &l, &o, &domino, &domino] {
+            tiling.add_piece(piece.clone()).unwrap();
+            pieces.push(piece.clone());
+            check(&mut tiling, &pieces);
+        }
+
+        // A seeded search gives a tiling that depends on the seed only
+        tiling.set_random_seed(Some(7));
+        let solution = tiling.solve().unwrap();
+        assert_eq!(tiling.solve().unwrap(), solution);
+        check(&mut tiling, &pieces);
+        let limits = SolveOptions {
+            max_nodes: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            tiling.solve_with_options(&limits),
+            SolveOutcome::LimitReached { .. }
+        ));
+
+        assert_eq!(
+            tiling.remove_piece(5),
+            Err(TilingError::PieceOutOfRange {
+                piece: 5,
+                n_pieces: 5
+            })
+        );
+        assert_eq!(
+            tiling.add_piece(vec![vec![1, 1], vec![1]]),
+            Err(TilingError::RaggedPiece { piece: 5 })
+        );
+    }
+
+    #[test]
+    fn test_parallel_search() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]]; 4];
+        // The parallel search splits that of DancingLinks, keeping its order
+        let tiling = PolyominoTiling::new(4, 2, dominoes).with_backend(Backend::DancingLinks);
+        let search = ParallelSearch::new().with_threads(2);
+        assert_eq!(tiling.count_solutions_parallel(&search).unwrap(), 120);
+        assert_eq!(
+            tiling.solutions_parallel(&search).unwrap(),
+            tiling.solutions().unwrap().collect::<Vec<_>>()
+        );
+
+        let tiling = PolyominoTiling::new(3, 3, vec![vec![vec![1, 1]]]);
+        assert_eq!(tiling.count_solutions_parallel(&search).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let solutions_seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = solutions_seen.clone();
+        let dominoes = vec![vec![vec![1, 1]], vec![vec![1, 1]]];
+        let tiling = PolyominoTiling::new(2, 2, dominoes)
+            .with_progress_callback(1, move |progress| {
+                sink.lock().unwrap().push(progress.solutions)
+            });
+        assert_eq!(tiling.count_solutions().unwrap(), 4);
+        let solutions_seen = solutions_seen.lock().unwrap();
+        assert!(!solutions_seen.is_empty());
+        assert!(solutions_seen.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(solutions_seen.iter().all(|&solutions| solutions <= 4));
+    }
+
+    #[test]
+    fn test_piece_quantities() {
+        _ = env_logger::builder().is_test(true).try_init();
+        // Three copies of the same domino tile a 2x3 board in 3 ways
+        let tiling =
+            PolyominoTiling::new(3, 2, vec![vec![vec![1, 1]]]).with_piece_quantities(vec![(3, 3)]);
+        assert_eq!(tiling.count_solutions().unwrap(), 3);
+        let solution = tiling.solve().unwrap().unwrap();
+        assert_eq!(solution.len(), 3);
+        assert!(solution.iter().all(|(piece_id, _)| *piece_id == 0));
+
+        // Up to two dominoes and up to two monominoes on a 1x3 board
+        let tiling = PolyominoTiling::new(3, 1, vec![vec![vec![1, 1]], vec![vec![1]]])
+            .with_piece_quantities(vec![(0, 2), (0, 2)]);
+        let mut solutions: Vec<Vec<Vec<Option<usize>>>> = tiling
+            .solutions()
+            .unwrap()
+            .map(|solution| piece_placements_to_matrix_of_piece_ids(&solution, 3, 1))
+            .collect();
+        solutions.sort();
+        assert_eq!(
+            solutions,
+            vec![
+                vec![vec![Some(0), Some(0), Some(1)]],
+                vec![vec![Some(1), Some(0), Some(0)]],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memoized_search() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]]; 4];
+        // Samples list their pieces in the order DancingLinks places them
+        let tiling = PolyominoTiling::new(4, 2, dominoes).with_backend(Backend::DancingLinks);
+        assert_eq!(tiling.count_solutions_memoized().unwrap(), 120);
+        let solutions: Vec<PiecePlacements> = tiling.solutions().unwrap().collect();
+        let samples = tiling.sample_solutions(20, 7).unwrap();
+        assert_eq!(samples.len(), 20);
+        assert!(samples.iter().all(|sample| solutions.contains(sample)));
+        assert_eq!(tiling.sample_solutions(20, 7).unwrap(), samples);
+
+        let tiling = PolyominoTiling::new(3, 3, vec![vec![vec![1, 1]]]);
+        assert_eq!(tiling.count_solutions_memoized().unwrap(), 0);
+        assert!(tiling.sample_solutions(3, 0).unwrap().is_empty());
+
+        let tiling =
+            PolyominoTiling::new(3, 2, vec![vec![vec![1, 1]]]).with_piece_quantities(vec![(3, 3)]);
+        assert_eq!(
+            tiling.count_solutions_memoized(),
+            Err(TilingError::Matrix(DlxError::MemoizationUnsupported))
+        );
+    }
+
+    #[test]
+    fn test_enumerate_with_checkpoints() {
+        _ = env_logger::builder().is_test(true).try_init();
+        let dominoes = vec![vec![vec![1, 1]]; 4];
+        // Checkpoints are taken on DancingLinks, whose order solutions then follow
+        let tiling =
+            PolyominoTiling::new(4, 2, dominoes.clone()).with_backend(Backend::DancingLinks);
+        let expected: Vec<PiecePlacements> = tiling.solutions().unwrap().collect();
+        let path = std::env::temp_dir().join(format!(
+            "polyomino-tiling-tiling-checkpoint-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let cancellation = CancellationToken::new();
+        let options = SolveOptions {
+            cancellation: Some(cancellation.clone()),
+            ..SolveOptions::default()
+        };
+        let mut found = Vec::new();
+        let outcome = tiling.enumerate_with_checkpoints(&path, Duration::ZERO, &options, |s| {
+            found.push(s);
+            if found.len() == 10 {
+                cancellation.cancel();
+            }
+        });
+        assert_eq!(outcome.unwrap(), Some(Limit::Cancelled));
+        assert_eq!(found.len(), 10);
+
+        let outcome = tiling.enumerate_with_checkpoints(
+            &path,
+            Duration::ZERO,
+            &SolveOptions::default(),
+            |s| found.push(s),
+        );
+        assert_eq!(outcome.unwrap(), None);
+        assert_eq!(found, expected);
+
+        // A different tiling can not resume the enumeration
+        let other = PolyominoTiling::new(2, 4, dominoes);
+        let error = other
+            .enumerate_with_checkpoints(&path, Duration::ZERO, &SolveOptions::default(), |_| {})
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
 }
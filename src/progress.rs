@@ -0,0 +1,76 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Snapshot of a running search, passed to progress callbacks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    /// Number of rows currently selected or left out, i.e. the length of `branches`
+    pub depth: usize,
+    /// Number of nodes of the search tree visited so far
+    pub nodes: u64,
+    /// Number of times a node was unlinked from its column so far
+    pub updates: u64,
+    /// Number of solutions found so far
+    pub solutions: u64,
+    /// Branch being explored at each level of the search, starting from the top
+    pub branches: Vec<Branch>,
+}
+
+impl Progress {
+    /// Estimates the fraction of the search tree explored so far, between 0 and 1.
+    ///
+    /// This is the estimate of Knuth's programs, which assumes that all the branches
+    /// at a level take the same time. It is rough, but good enough for a progress bar.
+    pub fn estimated_completion(&self) -> f64 {
+        let mut completion = 0.0;
+        let mut scale = 1.0;
+        for branch in &self.branches {
+            scale /= branch.count as f64;
+            completion += branch.index as f64 * scale;
+        }
+        completion + scale / 2.0
+    }
+}
+
+/// Position of a branch among the branches of its level, e.g. "row 3 of 17".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Branch {
+    /// 0-based index of the branch being explored
+    pub index: usize,
+    /// Number of branches of the level, including the one leaving the column
+    /// out when it may be covered fewer times
+    pub count: usize,
+}
+
+impl fmt::Display for Branch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {} of {}", self.index + 1, self.count)
+    }
+}
+
+type ProgressCallback = dyn FnMut(&Progress) + Send;
+
+/// A progress callback along with how often it fires.
+///
+/// Clones share the callback, so copies of a matrix report to the same place.
+#[derive(Clone)]
+pub(crate) struct ProgressReporter {
+    /// Number of updates between two calls
+    pub(crate) interval: u64,
+    callback: Arc<Mutex<ProgressCallback>>,
+}
+
+impl ProgressReporter {
+    /// Makes a reporter calling `callback` every `interval` updates, 0 being taken as 1.
+    pub(crate) fn new(interval: u64, callback: impl FnMut(&Progress) + Send + 'static) -> Self {
+        Self {
+            interval: interval.max(1),
+            callback: Arc::new(Mutex::new(callback)),
+        }
+    }
+
+    pub(crate) fn report(&self, progress: &Progress) {
+        let mut callback = self.callback.lock().unwrap_or_else(|err| err.into_inner());
+        callback(progress);
+    }
+}
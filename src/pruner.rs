@@ -0,0 +1,32 @@
+use crate::dancing_links::ActiveColumns;
+
+/// Check run by the search after each row it selects, which can cut off branches
+/// known to hold no solution, e.g. from domain knowledge the matrix does not show.
+///
+/// The search already gives up on a branch when a column has no row left. A pruner
+/// should only reject branches without any solution, or some solutions are lost.
+///
+/// Implementations must be `Clone` so that a `DancingLinks` holding one can be
+/// cloned, each copy getting its own pruner.
+pub trait Pruner: ClonePruner + Send {
+    /// Returns whether the search should go on after selecting `row`, `columns` being
+    /// the primary columns still to cover, not counting those left out.
+    fn keep(&mut self, row: usize, columns: ActiveColumns<'_>) -> bool;
+}
+
+/// Lets `Box<dyn Pruner>` be cloned, implemented for every `Clone` pruner.
+pub trait ClonePruner {
+    fn clone_box(&self) -> Box<dyn Pruner>;
+}
+
+impl<T: Pruner + Clone + 'static> ClonePruner for T {
+    fn clone_box(&self) -> Box<dyn Pruner> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Pruner> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
@@ -0,0 +1,41 @@
+/// Small seeded pseudo-random generator (SplitMix64), so that randomized searches
+/// are reproducible on every target without an extra dependency.
+#[derive(Clone, Debug)]
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`, which must not be empty.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        // The bias of the multiply-shift reduction is negligible for our sizes
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Like `below`, for `n` up to `u128::MAX`.
+    pub(crate) fn below_u128(&mut self, n: u128) -> u128 {
+        if n <= u64::MAX as u128 {
+            return (self.next_u64() as u128 * n) >> 64;
+        }
+        // Rejection sampling keeps the draw uniform beyond 64 bits
+        let bits = 128 - (n - 1).leading_zeros();
+        loop {
+            let x = ((self.next_u64() as u128) << 64 | self.next_u64() as u128) >> (128 - bits);
+            if x < n {
+                return x;
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use web_time::Duration;
+
+/// Measures of the effort spent by a search, to compare encodings and heuristics.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchStats {
+    /// Number of nodes of the search tree visited
+    pub nodes: u64,
+    /// Number of nodes visited at each depth, starting from the root
+    pub nodes_per_depth: Vec<u64>,
+    /// Number of times a node was unlinked from its column
+    pub updates: u64,
+    /// Number of times the search went back up to try another branch
+    pub backtracks: u64,
+    /// Number of solutions found
+    pub solutions: u64,
+    /// Number of nodes given up on by the `Pruner`
+    #[serde(default)]
+    pub pruned: u64,
+    /// Time spent searching, excluding the time between steps of a paused search
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    /// Returns the depth of the deepest node visited, the root being at depth 0.
+    pub fn max_depth(&self) -> usize {
+        self.nodes_per_depth.len().saturating_sub(1)
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "nodes:      {}", self.nodes)?;
+        writeln!(f, "updates:    {}", self.updates)?;
+        writeln!(f, "backtracks: {}", self.backtracks)?;
+        writeln!(f, "solutions:  {}", self.solutions)?;
+        writeln!(f, "pruned:     {}", self.pruned)?;
+        writeln!(f, "max depth:  {}", self.max_depth())?;
+        writeln!(f, "elapsed:    {:?}", self.elapsed)?;
+        write!(f, "nodes per depth: {:?}", self.nodes_per_depth)
+    }
+}
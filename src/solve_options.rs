@@ -0,0 +1,110 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use web_time::{Duration, Instant};
+
+/// Limits on a single search.
+///
+/// Every limit is optional, `SolveOptions::default()` searches until a solution is
+/// found or the search is exhausted.
+#[derive(Clone, Debug, Default)]
+pub struct SolveOptions {
+    /// Maximum number of nodes of the search tree to visit
+    pub max_nodes: Option<u64>,
+    /// Point in time after which the search gives up
+    pub deadline: Option<Instant>,
+    /// Token through which another thread can stop the search
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl SolveOptions {
+    /// Sets the deadline to `time_limit` from now.
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.deadline = Some(Instant::now() + time_limit);
+        self
+    }
+
+    /// Returns the limit the search has run into after visiting `nodes` nodes, if any.
+    pub(crate) fn reached_limit(&self, nodes: u64) -> Option<Limit> {
+        if self.max_nodes.is_some_and(|max_nodes| nodes >= max_nodes) {
+            return Some(Limit::Nodes);
+        }
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Some(Limit::Cancelled);
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Some(Limit::Deadline);
+        }
+        None
+    }
+}
+
+/// Shared flag that asks a running search to stop at its next check.
+///
+/// Clones share the same flag, so one can be handed to the search and another kept
+/// by whoever decides to cancel it.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The limit of `SolveOptions` that stopped a search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Nodes,
+    Deadline,
+    Cancelled,
+}
+
+/// Result of a search run under `SolveOptions`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveOutcome<T> {
+    Solved(T),
+    /// The whole search tree was explored without finding a solution
+    Unsolvable,
+    /// The search stopped early, so whether a solution exists is unknown
+    LimitReached {
+        limit: Limit,
+        /// Number of nodes of the search tree visited before stopping
+        nodes: u64,
+    },
+}
+
+impl<T> SolveOutcome<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> SolveOutcome<U> {
+        match self {
+            SolveOutcome::Solved(solution) => SolveOutcome::Solved(f(solution)),
+            SolveOutcome::Unsolvable => SolveOutcome::Unsolvable,
+            SolveOutcome::LimitReached { limit, nodes } => {
+                SolveOutcome::LimitReached { limit, nodes }
+            }
+        }
+    }
+
+    /// Returns the solution, if one was found.
+    pub fn solution(self) -> Option<T> {
+        match self {
+            SolveOutcome::Solved(solution) => Some(solution),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// Event of the search tree explored by a `DancingLinks` search, as recorded in a
+/// `SearchTrace`.
+///
+/// Nodes of the search tree are numbered in the order they are entered. Columns and
+/// rows are given by their index in the matrix.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// Node `id` is entered from node `parent` by selecting `row` there, or by
+    /// leaving the column of `parent` out when `row` is `None`. The root has no
+    /// parent.
+    Enter {
+        id: usize,
+        parent: Option<usize>,
+        depth: usize,
+        row: Option<usize>,
+    },
+    /// Node `id` branches on `column`, which has `degree` ways to go on
+    Choose {
+        id: usize,
+        column: usize,
+        degree: usize,
+    },
+    /// Node `id` is a dead end: `column` can not be covered any more
+    DeadEnd { id: usize, column: usize },
+    /// Node `id` was given up on by the `Pruner`
+    Pruned { id: usize },
+    /// Node `id` is an exact cover made of `rows`
+    Solution { id: usize, rows: Vec<usize> },
+    /// The search backtracks out of node `id`, below which `hidden` nodes deeper
+    /// than the depth limit were visited without being recorded
+    Leave { id: usize, hidden: u64 },
+}
+
+/// Search tree explored by a `DancingLinks` search, down to a depth limit, see
+/// `DancingLinks::enable_trace`.
+///
+/// It is a stream of `TraceEvent`s in the order they happened, which can be
+/// replayed as an animation of the search, or drawn as a Graphviz graph with
+/// `write_dot`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchTrace {
+    max_depth: usize,
+    events: Vec<TraceEvent>,
+    /// Recorded nodes from the root down to the current one, as depth, id and
+    /// number of hidden nodes below
+    path: Vec<(usize, usize, u64)>,
+    n_nodes: usize,
+    /// Names of the columns and rows, filled in when the trace is taken
+    column_names: Vec<String>,
+    row_names: Vec<String>,
+}
+
+impl SearchTrace {
+    pub(crate) fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the deepest level recorded, the root being at depth 0.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Forgets the events recorded so far, for a new search.
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+        self.path.clear();
+        self.n_nodes = 0;
+    }
+
+    /// Records entering a node at `depth` by selecting `row`, returning its id, or
+    /// `None` when it is deeper than the limit.
+    pub(crate) fn enter(&mut self, depth: usize, row: Option<usize>) -> Option<usize> {
+        self.leave_down_to(depth);
+        if depth > self.max_depth {
+            if let Some(deepest) = self.path.last_mut() {
+                deepest.2 += 1;
+            }
+            return None;
+        }
+        let id = self.n_nodes;
+        self.n_nodes += 1;
+        self.events.push(TraceEvent::Enter {
+            id,
+            parent: self.path.last().map(|&(_, parent, _)| parent),
+            depth,
+            row,
+        });
+        self.path.push((depth, id, 0));
+        Some(id)
+    }
+
+    pub(crate) fn record(&mut self, event: TraceEvent) {
+        self.events.push(event);
+    }
+
+    /// Records leaving every node still on the path, once the search is exhausted.
+    pub(crate) fn finish(&mut self) {
+        self.leave_down_to(0);
+    }
+
+    /// Records leaving the nodes of the path at `depth` or deeper.
+    fn leave_down_to(&mut self, depth: usize) {
+        while self.path.last().is_some_and(|&(d, _, _)| d >= depth) {
+            let (_, id, hidden) = self.path.pop().unwrap();
+            if let Some(parent) = self.path.last_mut() {
+                parent.2 += hidden;
+            }
+            self.events.push(TraceEvent::Leave { id, hidden });
+        }
+    }
+
+    pub(crate) fn set_names(&mut self, column_names: Vec<String>, row_names: Vec<String>) {
+        self.column_names = column_names;
+        self.row_names = row_names;
+    }
+
+    /// Writes the events as JSON, one object per line, e.g.
+    /// `{"event":"choose","id":0,"column":3,"degree":2}`.
+    pub fn write_json(&self, mut writer: impl Write) -> io::Result<()> {
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, event)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the search tree as a Graphviz graph, which `dot -Tsvg` draws.
+    ///
+    /// Each node shows the column it branches on and its number of branches, edges
+    /// the row selected. Solutions are green, dead ends red, pruned nodes orange,
+    /// and nodes under which some were not recorded tell how many.
+    pub fn write_dot(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "digraph search {{")?;
+        writeln!(writer, "  node [shape=box, fontname=\"monospace\"];")?;
+        for event in &self.events {
+            match event {
+                TraceEvent::Enter {
+                    id, parent, row, ..
+                } => {
+                    if let Some(parent) = parent {
+                        let label = match row {
+                            Some(row) => self.row_name(*row),
+                            None => "(left out)".to_string(),
+                        };
+                        writeln!(
+                            writer,
+                            "  n{} -> n{} [label=\"{}\"];",
+                            parent,
+                            id,
+                            escape(&label)
+                        )?;
+                    }
+                }
+                TraceEvent::Choose { id, column, degree } => writeln!(
+                    writer,
+                    "  n{} [label=\"{} ({})\"];",
+                    id,
+                    escape(&self.column_name(*column)),
+                    degree
+                )?,
+                TraceEvent::DeadEnd { id, column } => writeln!(
+                    writer,
+                    "  n{} [label=\"{} (0)\", color=red];",
+                    id,
+                    escape(&self.column_name(*column))
+                )?,
+                TraceEvent::Pruned { id } => {
+                    writeln!(writer, "  n{} [label=\"pruned\", color=orange];", id)?
+                }
+                TraceEvent::Solution { id, .. } => writeln!(
+                    writer,
+                    "  n{} [label=\"solution\", style=filled, fillcolor=palegreen];",
+                    id
+                )?,
+                TraceEvent::Leave { id, hidden } if *hidden > 0 => {
+                    writeln!(writer, "  n{} [xlabel=\"+{} deeper\"];", id, hidden)?
+                }
+                TraceEvent::Leave { .. } => {}
+            }
+        }
+        writeln!(writer, "}}")
+    }
+
+    fn column_name(&self, column: usize) -> String {
+        self.column_names
+            .get(column)
+            .cloned()
+            .unwrap_or_else(|| column.to_string())
+    }
+
+    fn row_name(&self, row: usize) -> String {
+        self.row_names
+            .get(row)
+            .cloned()
+            .unwrap_or_else(|| row.to_string())
+    }
+}
+
+/// Escapes `text` for a double-quoted Graphviz string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth_limit() {
+        let mut trace = SearchTrace::new(1);
+        assert_eq!(trace.enter(0, None), Some(0));
+        assert_eq!(trace.enter(1, Some(4)), Some(1));
+        assert_eq!(trace.enter(2, Some(5)), None);
+        assert_eq!(trace.enter(3, Some(6)), None);
+        assert_eq!(trace.enter(1, None), Some(2));
+        trace.finish();
+        assert_eq!(
+            trace.events(),
+            [
+                TraceEvent::Enter {
+                    id: 0,
+                    parent: None,
+                    depth: 0,
+                    row: None
+                },
+                TraceEvent::Enter {
+                    id: 1,
+                    parent: Some(0),
+                    depth: 1,
+                    row: Some(4)
+                },
+                TraceEvent::Leave { id: 1, hidden: 2 },
+                TraceEvent::Enter {
+                    id: 2,
+                    parent: Some(0),
+                    depth: 1,
+                    row: None
+                },
+                TraceEvent::Leave { id: 2, hidden: 0 },
+                TraceEvent::Leave { id: 0, hidden: 2 },
+            ]
+        );
+
+        let mut json = Vec::new();
+        trace.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert_eq!(
+            json.lines().next(),
+            Some(r#"{"event":"enter","id":0,"parent":null,"depth":0,"row":null}"#)
+        );
+        let events: Vec<TraceEvent> = json
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events, trace.events());
+    }
+}
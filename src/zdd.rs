@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rng::SplitMix64;
+
+/// Node standing for the empty family, which has no set at all
+pub const EMPTY: usize = 0;
+/// Node standing for the family holding only the empty set
+pub const UNIT: usize = 1;
+
+/// Family of sets of rows, such as all exact covers of a matrix, stored as a
+/// zero-suppressed decision diagram.
+///
+/// Each node other than `EMPTY` and `UNIT` stands for the sets of its `lo` child,
+/// along with those of its `hi` child to which its row is added. Children always
+/// come before their parents in `nodes`, and no node has `EMPTY` as `hi` child or
+/// the same row and children as another.
+///
+/// Rows are not required to appear in the same order on every path, so the diagram
+/// is not canonical: two diagrams of the same family can differ.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Zdd {
+    nodes: Vec<ZddNode>,
+    root: usize,
+}
+
+/// A branch node of a `Zdd`, see there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ZddNode {
+    pub row: usize,
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl Zdd {
+    /// Returns every node, indexed by their id. The first two are placeholders for
+    /// `EMPTY` and `UNIT`.
+    pub fn nodes(&self) -> &[ZddNode] {
+        &self.nodes
+    }
+
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    /// Counts the sets of the family.
+    pub fn count(&self) -> u128 {
+        self.counts()[self.root]
+    }
+
+    /// Counts the sets of the family that contain `row`.
+    pub fn count_containing(&self, row: usize) -> u128 {
+        let counts = self.counts();
+        let mut containing = vec![0; self.nodes.len()];
+        for id in UNIT + 1..self.nodes.len() {
+            let node = self.nodes[id];
+            let with_row = if node.row == row {
+                counts[node.hi]
+            } else {
+                containing[node.hi]
+            };
+            containing[id] = containing[node.lo] + with_row;
+        }
+        containing[self.root]
+    }
+
+    /// Whether the family contains the set of rows `rows`, given in any order.
+    pub fn contains(&self, rows: &[usize]) -> bool {
+        let mut rows = rows.to_vec();
+        rows.sort_unstable();
+        rows.dedup();
+
+        // The node's row decides which child can hold the set
+        let mut n_found = 0;
+        let mut id = self.root;
+        while id > UNIT {
+            let node = self.nodes[id];
+            if rows.binary_search(&node.row).is_ok() {
+                n_found += 1;
+                id = node.hi;
+            } else {
+                id = node.lo;
+            }
+        }
+        id == UNIT && n_found == rows.len()
+    }
+
+    /// Calls `f` with every set of the family, its rows in the order they appear
+    /// along the path to `UNIT`.
+    pub fn for_each_solution(&self, mut f: impl FnMut(&[usize])) {
+        let mut rows = Vec::new();
+        self.visit(self.root, &mut rows, &mut f);
+    }
+
+    fn visit(&self, mut id: usize, rows: &mut Vec<usize>, f: &mut impl FnMut(&[usize])) {
+        while id > UNIT {
+            let node = self.nodes[id];
+            rows.push(node.row);
+            self.visit(node.hi, rows, f);
+            rows.pop();
+            id = node.lo;
+        }
+        if id == UNIT {
+            f(rows);
+        }
+    }
+
+    /// Draws `n` sets of the family uniformly at random, independently of each other,
+    /// from `seed`. Returns no set if the family is empty.
+    pub fn sample(&self, n: usize, seed: u64) -> Vec<Vec<usize>> {
+        let counts = self.counts();
+        if counts[self.root] == 0 {
+            return Vec::new();
+        }
+        let mut rng = SplitMix64::new(seed);
+        (0..n)
+            .map(|_| {
+                let mut rows = Vec::new();
+                let mut id = self.root;
+                while id > UNIT {
+                    // Go up with probability count(hi) / count(node)
+                    let node = self.nodes[id];
+                    if rng.below_u128(counts[id]) < counts[node.hi] {
+                        rows.push(node.row);
+                        id = node.hi;
+                    } else {
+                        id = node.lo;
+                    }
+                }
+                rows
+            })
+            .collect()
+    }
+
+    /// Returns the number of sets below each node.
+    fn counts(&self) -> Vec<u128> {
+        let mut counts = vec![0; self.nodes.len()];
+        counts[UNIT] = 1;
+        for id in UNIT + 1..self.nodes.len() {
+            let node = self.nodes[id];
+            counts[id] = counts[node.lo] + counts[node.hi];
+        }
+        counts
+    }
+}
+
+/// Builds a `Zdd` bottom-up, sharing nodes with the same row and children.
+pub(crate) struct ZddBuilder {
+    nodes: Vec<ZddNode>,
+    unique: HashMap<ZddNode, usize>,
+}
+
+impl ZddBuilder {
+    pub(crate) fn new() -> Self {
+        let terminal = ZddNode {
+            row: usize::MAX,
+            lo: EMPTY,
+            hi: EMPTY,
+        };
+        Self {
+            nodes: vec![terminal, terminal],
+            unique: HashMap::new(),
+        }
+    }
+
+    /// Returns the node for the sets of `lo`, along with those of `hi` with `row`.
+    pub(crate) fn node(&mut self, row: usize, lo: usize, hi: usize) -> usize {
+        if hi == EMPTY {
+            return lo;
+        }
+        let node = ZddNode { row, lo, hi };
+        *self.unique.entry(node).or_insert_with(|| {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        })
+    }
+
+    pub(crate) fn finish(self, root: usize) -> Zdd {
+        Zdd {
+            nodes: self.nodes,
+            root,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The family {{0, 1}, {0, 2}, {3}}
+    fn small_family() -> Zdd {
+        let mut builder = ZddBuilder::new();
+        let one_or_two = builder.node(2, EMPTY, UNIT);
+        let one_or_two = builder.node(1, one_or_two, UNIT);
+        let three = builder.node(3, EMPTY, UNIT);
+        let root = builder.node(0, three, one_or_two);
+        assert_eq!(builder.node(0, three, one_or_two), root);
+        assert_eq!(builder.node(5, three, EMPTY), three);
+        builder.finish(root)
+    }
+
+    #[test]
+    fn test_zdd_queries() {
+        let zdd = small_family();
+        assert_eq!(zdd.nodes().len(), 6);
+        assert_eq!(zdd.count(), 3);
+        assert_eq!(zdd.count_containing(0), 2);
+        assert_eq!(zdd.count_containing(2), 1);
+        assert_eq!(zdd.count_containing(4), 0);
+
+        assert!(zdd.contains(&[1, 0]));
+        assert!(zdd.contains(&[3]));
+        assert!(!zdd.contains(&[0]));
+        assert!(!zdd.contains(&[0, 1, 2]));
+        assert!(!zdd.contains(&[3, 4]));
+        assert!(!zdd.contains(&[]));
+
+        let mut sets = Vec::new();
+        zdd.for_each_solution(|rows| sets.push(rows.to_vec()));
+        assert_eq!(sets, vec![vec![0, 1], vec![0, 2], vec![3]]);
+    }
+
+    #[test]
+    fn test_zdd_sample() {
+        let zdd = small_family();
+        let samples = zdd.sample(3000, 42);
+        assert_eq!(samples.len(), 3000);
+        assert!(samples.iter().all(|rows| zdd.contains(rows)));
+        // Each of the 3 sets is drawn about 1000 times
+        for set in [vec![0, 1], vec![0, 2], vec![3]] {
+            let n = samples.iter().filter(|&rows| *rows == set).count();
+            assert!((850..1150).contains(&n), "{:?} drawn {} times", set, n);
+        }
+        assert_eq!(zdd.sample(3, 42), samples[..3]);
+
+        let empty = ZddBuilder::new().finish(EMPTY);
+        assert_eq!(empty.count(), 0);
+        assert!(empty.sample(5, 0).is_empty());
+        let unit = ZddBuilder::new().finish(UNIT);
+        assert_eq!(unit.sample(2, 0), vec![Vec::<usize>::new(); 2]);
+        assert!(unit.contains(&[]));
+    }
+}
@@ -3,9 +3,13 @@ import init, {
   get_predefined_polyomino,
 } from './pkg/polyomino_tiling.js';
 
+const SOLVE_TIME_LIMIT_MS = 10000;
+
 class PolyominoApp {
   constructor() {
     this.solver = null;
+    // Names of the pieces added to the solver, in the order of their ids
+    this.solverPieces = [];
     this.selectedPieces = [];
     this.customPolyominoes = [];
     this.isInitialized = false;
@@ -241,28 +245,10 @@ class PolyominoApp {
     document.getElementById('solve-btn').disabled = true;
 
     try {
-      // Create solver
-      this.solver = new PolyominoSolver(width, height);
-
-      // Add selected polyominoes
-      for (const pieceName of this.selectedPieces) {
-        if (pieceName.startsWith('custom_')) {
-          // Custom polyomino
-          const customMino = this.customPolyominoes.find(
-            (m) => m.name === pieceName
-          );
-          if (customMino) {
-            this.solver.add_polyomino(customMino.matrix);
-          }
-        } else {
-          // Predefined polyomino
-          const polyomino = get_predefined_polyomino(pieceName);
-          this.solver.add_polyomino(polyomino);
-        }
-      }
+      this.syncSolver(width, height);
 
-      // Solve
-      const solution = this.solver.solve();
+      // Solve, giving up if the search takes too long
+      const solution = this.solver.solve_with_limits(null, SOLVE_TIME_LIMIT_MS);
 
       if (solution === null) {
         this.updateStatus(
@@ -285,6 +271,49 @@ class PolyominoApp {
     }
   }
 
+  // Brings the pieces of the solver in line with the selection, only adding and
+  // removing those that changed since the last solve
+  syncSolver(width, height) {
+    if (
+      this.solver === null ||
+      this.solverWidth !== width ||
+      this.solverHeight !== height
+    ) {
+      this.solver = new PolyominoSolver(width, height);
+      this.solverWidth = width;
+      this.solverHeight = height;
+      this.solverPieces = [];
+    }
+
+    for (let id = this.solverPieces.length - 1; id >= 0; id--) {
+      if (!this.selectedPieces.includes(this.solverPieces[id])) {
+        this.solver.remove_polyomino(id);
+        this.solverPieces.splice(id, 1);
+      }
+    }
+
+    for (const pieceName of this.selectedPieces) {
+      if (this.solverPieces.includes(pieceName)) {
+        continue;
+      }
+      if (pieceName.startsWith('custom_')) {
+        // Custom polyomino
+        const customMino = this.customPolyominoes.find(
+          (m) => m.name === pieceName
+        );
+        if (customMino) {
+          this.solver.add_polyomino(customMino.matrix);
+          this.solverPieces.push(pieceName);
+        }
+      } else {
+        // Predefined polyomino
+        const polyomino = get_predefined_polyomino(pieceName);
+        this.solver.add_polyomino(polyomino);
+        this.solverPieces.push(pieceName);
+      }
+    }
+  }
+
   displaySolution(solution) {
     const width = parseInt(document.getElementById('board-width').value);
     const height = parseInt(document.getElementById('board-height').value);
@@ -313,7 +342,7 @@ class PolyominoApp {
           cell.classList.add('empty');
         } else {
           cell.classList.add(`piece-${pieceId}`);
-          cell.title = `Piece ${this.selectedPieces[pieceId]} (ID: ${pieceId})`;
+          cell.title = `Piece ${this.solverPieces[pieceId]} (ID: ${pieceId})`;
         }
 
         board.appendChild(cell);
@@ -661,6 +690,13 @@ class PolyominoApp {
       (m) => m.name !== fullName
     );
 
+    // Remove from the solver, as a new piece may take the same name
+    const solverId = this.solverPieces.indexOf(fullName);
+    if (solverId !== -1) {
+      this.solver.remove_polyomino(solverId);
+      this.solverPieces.splice(solverId, 1);
+    }
+
     // Remove from selected pieces if it was selected
     const index = this.selectedPieces.indexOf(fullName);
     if (index !== -1) {